@@ -1,28 +1,68 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
-use std::io::{self, IsTerminal, Write};
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
-use std::str::FromStr;
+use std::process::Command;
 
 use age::x25519;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
 use chrono::{SecondsFormat, Utc};
 use clap::{Parser, Subcommand, ValueEnum};
-use secrecy::{ExposeSecret, SecretString};
+use secrecy::{ExposeSecret, SecretSlice, SecretString};
+use zeroize::Zeroizing;
 
-use crate::crypto::{decrypt_value, encrypt_value};
+use crate::crypto::{canonical_pubkey, is_x25519_pubkey, parse_recipient};
+use crate::dotenv;
+use crate::edit::{self, EditChange, PrivateDir};
+use crate::envelope::{DataKey, EnvDecryptor, digest_hex};
 use crate::error::{EnvkeyError, Result};
+use crate::export::{ExportFormat, render as render_export};
+use crate::git::{self, repo_root};
 use crate::identity::{
-    default_identity_path, detect_username, expand_home_prefix, load_identity_from,
-    load_or_generate_identity, resolve_identity_path,
+    IdentityBundle, IdentityFile, default_identity_path, detect_username, env_identity_key,
+    env_identity_paths, expand_home_prefix, identity_exists, load_identities,
+    load_or_generate_identity, new_passphrase, resolve_identity_path,
 };
-use crate::model::{EnvkeyFile, Role, SecretEntry, TeamMember};
-use crate::storage::{envkey_path, read_envkey, with_envkey_lock, write_envkey_atomic};
+use crate::merge::{MergeResult, RekeyEnv, merge_files};
+use crate::model::{
+    EnvkeyFile, FORMAT_VERSION, Role, SIGNED_FORMAT_VERSION, SecretEncoding, SecretEntry,
+    TeamMember,
+};
+use crate::output::{
+    DiffComparison, DiffEntry, DiffReport, DiffValue, EditReport, ExportReport, GetReport,
+    GitInstallReport, IdentityReport, ImportReport, InitReport, KeyChange, LsReport, MemberInfo,
+    MemberReport, MembersReport, MigrateReport, MvReport, OutputFormat, ProjectInfo,
+    ProjectsReport, PromoteReport, Reencrypted, RemovedKeys, RmReport, RotateReport, SecretInfo,
+    SetReport, TrustReport,
+};
+use crate::project::{PROJECT_FILE_NAME, ProjectConfig, project_envkey_path};
+use crate::roster::{
+    ensure_current_roster, reseal_roster, seal_roster, sign_member, verify_members, verify_roster,
+};
+use crate::storage::{
+    discover_envkey_path, envkey_path, explicit_envkey_path, missing_envkey, parse_envkey,
+    read_envkey, read_trusted_envkey, read_unverified_envkey, verify_envkey_roster,
+    with_envkey_lock, write_envkey_atomic, write_private_file,
+};
+use crate::trust::{KnownRoster, trust_roster};
 
 #[derive(Debug, Parser)]
 #[command(name = "envkey", version, about = "Secrets without servers")]
 pub struct Cli {
-    /// Identity key file to use for this command
+    /// Identity key file to use for this command; repeat to try several in order
     #[arg(long, global = true)]
-    identity: Option<PathBuf>,
+    identity: Vec<PathBuf>,
+    /// .envkey file to use instead of searching up from the current directory
+    #[arg(long, global = true, value_name = "PATH")]
+    file: Option<PathBuf>,
+    /// Project from envkey.toml whose .envkey file to use
+    #[arg(long, global = true, value_name = "NAME", conflicts_with = "file")]
+    project: Option<String>,
+    /// Print results as a table, or as JSON or YAML for scripts
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormatArg::Table)]
+    output_format: OutputFormatArg,
     #[command(subcommand)]
     command: Commands,
 }
@@ -34,22 +74,154 @@ enum Commands {
         /// Force identity regeneration (blocked if .envkey already exists)
         #[arg(long)]
         force: bool,
+        /// Encrypt the new identity with a passphrase
+        #[arg(long)]
+        passphrase: bool,
     },
     /// Encrypt and store a secret key/value pair
     Set {
         #[arg(short = 'e', long = "env", default_value = "default")]
         env: String,
         key: String,
-        value: String,
+        /// Secret value; use `-` to read stdin, or omit to be prompted without echo
+        value: Option<String>,
+        /// Read the value from a file, byte for byte
+        #[arg(long, value_name = "PATH", conflicts_with = "value")]
+        from_file: Option<PathBuf>,
+        /// Store the value as binary even if it is valid UTF-8
+        #[arg(long)]
+        binary: bool,
     },
     /// Decrypt and print a secret value
     Get {
         #[arg(short = 'e', long = "env", default_value = "default")]
         env: String,
         key: String,
+        /// Write the exact stored bytes to stdout, without a trailing newline
+        #[arg(long)]
+        raw: bool,
+        /// Write the exact stored bytes to this file (mode 0600)
+        #[arg(short = 'o', long = "output", value_name = "FILE", conflicts_with = "raw")]
+        output_file: Option<PathBuf>,
+    },
+    /// Edit an environment as a dotenv file in $VISUAL or $EDITOR
+    Edit {
+        #[arg(short = 'e', long = "env", default_value = "default")]
+        env: String,
+    },
+    /// Delete secrets
+    Rm {
+        #[arg(short = 'e', long = "env", default_value = "default", conflicts_with = "all_envs")]
+        env: String,
+        #[arg(required = true)]
+        keys: Vec<String>,
+        /// Delete the keys from every environment that has them
+        #[arg(long)]
+        all_envs: bool,
     },
-    /// List secret keys and metadata
+    /// Rename a secret
+    Mv {
+        #[arg(short = 'e', long = "env", default_value = "default", conflicts_with = "all_envs")]
+        env: String,
+        old: String,
+        new: String,
+        /// Rename the key in every environment that has it
+        #[arg(long)]
+        all_envs: bool,
+    },
+    /// List secret keys and metadata (all environments unless --env is given)
     Ls {
+        #[arg(short = 'e', long = "env")]
+        env: Option<String>,
+        /// Summarise every project listed in envkey.toml instead
+        #[arg(long, conflicts_with = "env")]
+        all_projects: bool,
+    },
+    /// Run a command with decrypted secrets added to its environment
+    Run {
+        #[arg(short = 'e', long = "env", default_value = "default")]
+        env: String,
+        /// Only inject these keys (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        only: Vec<String>,
+        /// Do not inject these keys (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        exclude: Vec<String>,
+        /// Replace variables that are already set in the environment
+        #[arg(long = "override")]
+        override_existing: bool,
+        /// Command and arguments to run, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Encrypt every entry of a dotenv file into an environment
+    Import {
+        #[arg(short = 'e', long = "env", default_value = "default")]
+        env: String,
+        path: PathBuf,
+        /// Keep existing values when a key is already set to something else
+        #[arg(long, conflicts_with = "overwrite")]
+        skip_existing: bool,
+        /// Replace existing values with the imported ones
+        #[arg(long)]
+        overwrite: bool,
+        /// Show what would change without writing .envkey
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Copy secrets from one environment into another, re-encrypting them for its recipients
+    #[command(alias = "cp")]
+    Promote {
+        from: String,
+        to: String,
+        /// Keys to copy (default: every key in the source environment)
+        keys: Vec<String>,
+        /// Replace values that already differ in the destination
+        #[arg(long)]
+        overwrite: bool,
+        /// Show what would change without writing .envkey
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Decrypt an environment and print it in a file format
+    Export {
+        #[arg(short = 'e', long = "env", default_value = "default")]
+        env: String,
+        #[arg(long, value_enum, default_value_t = ExportFormatArg::Dotenv)]
+        format: ExportFormatArg,
+        /// Write to this file (mode 0600) instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "FILE")]
+        output_file: Option<PathBuf>,
+    },
+    /// Show which keys differ between two environments, or against another file or git revision
+    Diff {
+        /// Two environments to compare, or one (default: all) with --rev or --against
+        #[arg(value_name = "ENV", num_args = 0..=2)]
+        envs: Vec<String>,
+        /// Compare against .envkey as committed at this git revision
+        #[arg(long, value_name = "REV", conflicts_with = "against")]
+        rev: Option<String>,
+        /// Compare against another .envkey file
+        #[arg(long, value_name = "PATH")]
+        against: Option<PathBuf>,
+        /// Print decrypted values of changed keys
+        #[arg(long)]
+        show_values: bool,
+    },
+    /// Upgrade .envkey to the newest format version
+    Migrate {
+        /// Sign a team roster from before version 2 without asking
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Trust the current team roster after its root admin changed
+    Trust {
+        /// Trust it without asking
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Replace an environment's data key and re-encrypt its secrets under the new key
+    Rotate {
         #[arg(short = 'e', long = "env", default_value = "default")]
         env: String,
     },
@@ -58,6 +230,48 @@ enum Commands {
         #[command(subcommand)]
         command: MemberCommands,
     },
+    /// Manage the local identity key file
+    Identity {
+        #[command(subcommand)]
+        command: IdentityCommands,
+    },
+    /// Set up git integration for .envkey files
+    Git {
+        #[command(subcommand)]
+        command: GitCommands,
+    },
+    /// Three-way merge of .envkey files, run by git as a merge driver
+    MergeDriver {
+        /// Common ancestor (%O)
+        base: PathBuf,
+        /// Our version (%A); the merge result is written here
+        ours: PathBuf,
+        /// Their version (%B)
+        theirs: PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum IdentityCommands {
+    /// Set, change or remove the passphrase protecting the identity file
+    Passwd {
+        /// Store the identity unencrypted again
+        #[arg(long)]
+        remove: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum GitCommands {
+    /// Register the envkey merge and diff drivers in .gitattributes and the local git config
+    Install,
+    /// Render an .envkey file as readable text for `git diff`
+    DiffTextconv {
+        file: PathBuf,
+        /// Show decrypted values instead of digests of them
+        #[arg(long)]
+        show_values: bool,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -65,9 +279,13 @@ enum MemberCommands {
     /// Add a team member and re-encrypt secrets for new recipients
     Add {
         name: String,
+        /// age or age plugin public key, or an ssh-ed25519/ssh-rsa public key (quoted)
         pubkey: Option<String>,
         #[arg(long, value_enum, default_value_t = MemberRoleArg::Member)]
         role: MemberRoleArg,
+        /// Restrict access to these environments (repeatable; default: all environments)
+        #[arg(short = 'e', long = "env")]
+        envs: Vec<String>,
     },
     /// Update a team member public key and re-encrypt secrets
     Update { name: String, pubkey: String },
@@ -82,6 +300,18 @@ enum MemberCommands {
         #[arg(long)]
         yes: bool,
     },
+    /// Give a member access to environments and re-encrypt them for the member
+    Grant {
+        name: String,
+        #[arg(required = true)]
+        envs: Vec<String>,
+    },
+    /// Remove a member's access to environments and re-encrypt them without the member
+    Revoke {
+        name: String,
+        #[arg(required = true)]
+        envs: Vec<String>,
+    },
     /// List team members
     Ls,
 }
@@ -104,6 +334,44 @@ enum MemberRoleArg {
     Readonly,
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ExportFormatArg {
+    Dotenv,
+    Shell,
+    Json,
+    Yaml,
+    GithubEnv,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OutputFormatArg {
+    Table,
+    Json,
+    Yaml,
+}
+
+impl From<OutputFormatArg> for OutputFormat {
+    fn from(value: OutputFormatArg) -> Self {
+        match value {
+            OutputFormatArg::Table => OutputFormat::Table,
+            OutputFormatArg::Json => OutputFormat::Json,
+            OutputFormatArg::Yaml => OutputFormat::Yaml,
+        }
+    }
+}
+
+impl From<ExportFormatArg> for ExportFormat {
+    fn from(value: ExportFormatArg) -> Self {
+        match value {
+            ExportFormatArg::Dotenv => ExportFormat::Dotenv,
+            ExportFormatArg::Shell => ExportFormat::Shell,
+            ExportFormatArg::Json => ExportFormat::Json,
+            ExportFormatArg::Yaml => ExportFormat::Yaml,
+            ExportFormatArg::GithubEnv => ExportFormat::GithubEnv,
+        }
+    }
+}
+
 impl From<MemberRoleArg> for Role {
     fn from(value: MemberRoleArg) -> Self {
         match value {
@@ -117,47 +385,170 @@ impl From<MemberRoleArg> for Role {
 
 pub fn run() -> Result<()> {
     let cli = Cli::parse();
-    let identity_override = cli.identity.as_deref();
+    let identity_override = cli.identity.as_slice();
+    let output = OutputFormat::from(cli.output_format);
+    let cwd = env::current_dir()?;
+    let explicit_path = match cli.project.as_deref() {
+        Some(project) => Some(project_envkey_path(&cwd, project)?),
+        None => explicit_envkey_path(cli.file.as_deref())?,
+    };
 
-    match cli.command {
-        Commands::Init { force } => cmd_init(force, identity_override),
-        Commands::Set { env, key, value } => cmd_set(&env, &key, value, identity_override),
-        Commands::Get { env, key } => cmd_get(&env, &key, identity_override),
-        Commands::Ls { env } => cmd_ls(&env),
-        Commands::Member { command } => cmd_member(command, identity_override),
-    }
-}
+    // `init` creates the file in place rather than adopting one from a parent directory.
+    let envkey_path = match cli.command {
+        Commands::Init { force, passphrase } => {
+            let envkey_path = explicit_path.unwrap_or_else(|| envkey_path(&cwd));
+            return cmd_init(&envkey_path, force, passphrase, identity_override, output);
+        }
+        Commands::Ls { all_projects: true, .. } => return cmd_ls_projects(&cwd, output),
+        _ => explicit_path.unwrap_or_else(|| discover_envkey_path(&cwd)),
+    };
+    let envkey_path = envkey_path.as_path();
 
-fn cmd_member(command: MemberCommands, identity_override: Option<&Path>) -> Result<()> {
-    match command {
-        MemberCommands::Add { name, pubkey, role } => {
-            cmd_member_add(&name, pubkey.as_deref(), role.into(), identity_override)
+    match cli.command {
+        Commands::Init { .. } => unreachable!("init is handled above"),
+        Commands::Set { env, key, value, from_file, binary } => {
+            let (secret, encoding) = read_secret_value(&key, value, from_file.as_deref(), binary)?;
+            cmd_set(envkey_path, &env, &key, secret, encoding, identity_override, output)
         }
-        MemberCommands::Update { name, pubkey } => {
-            cmd_member_update(&name, &pubkey, identity_override)
+        Commands::Get { env, key, raw, output_file } => {
+            cmd_get(envkey_path, &env, &key, raw, output_file.as_deref(), identity_override, output)
+        }
+        Commands::Edit { env } => cmd_edit(envkey_path, &env, identity_override, output),
+        Commands::Rm { env, keys, all_envs } => {
+            cmd_rm(envkey_path, (!all_envs).then_some(env.as_str()), &keys, output)
+        }
+        Commands::Mv { env, old, new, all_envs } => cmd_mv(
+            envkey_path,
+            (!all_envs).then_some(env.as_str()),
+            &old,
+            &new,
+            identity_override,
+            output,
+        ),
+        Commands::Ls { env, .. } => cmd_ls(envkey_path, env.as_deref(), output),
+        Commands::Run { env, only, exclude, override_existing, command } => cmd_run(
+            envkey_path,
+            &env,
+            &only,
+            &exclude,
+            override_existing,
+            &command,
+            identity_override,
+        ),
+        Commands::Import { env, path, skip_existing, overwrite, dry_run } => {
+            let policy = if overwrite {
+                ImportPolicy::Overwrite
+            } else if skip_existing {
+                ImportPolicy::SkipExisting
+            } else {
+                ImportPolicy::Reject
+            };
+            let options = ImportOptions { policy, dry_run };
+            cmd_import(envkey_path, &env, &path, options, identity_override, output)
+        }
+        Commands::Promote { from, to, keys, overwrite, dry_run } => {
+            let policy = if overwrite { ImportPolicy::Overwrite } else { ImportPolicy::Reject };
+            let options = ImportOptions { policy, dry_run };
+            cmd_promote(envkey_path, &from, &to, &keys, options, identity_override, output)
+        }
+        Commands::Export { env, format, output_file } => cmd_export(
+            envkey_path,
+            &env,
+            format.into(),
+            output_file.as_deref(),
+            identity_override,
+            output,
+        ),
+        Commands::Diff { envs, rev, against, show_values } => {
+            let other = match (rev, against) {
+                (Some(rev), _) => DiffTarget::Revision(rev),
+                (None, Some(path)) => DiffTarget::File(path),
+                (None, None) => DiffTarget::Environments,
+            };
+            cmd_diff(envkey_path, &envs, other, show_values, identity_override, output)
+        }
+        Commands::Migrate { yes } => cmd_migrate(envkey_path, yes, identity_override, output),
+        Commands::Trust { yes } => cmd_trust(envkey_path, yes, output),
+        Commands::Rotate { env } => cmd_rotate(envkey_path, &env, identity_override, output),
+        Commands::Member { command } => cmd_member(envkey_path, command, identity_override, output),
+        Commands::Identity { command: IdentityCommands::Passwd { remove } } => {
+            cmd_identity_passwd(remove, identity_override, output)
+        }
+        Commands::Git { command: GitCommands::Install } => cmd_git_install(&cwd, output),
+        Commands::Git { command: GitCommands::DiffTextconv { file, show_values } } => {
+            cmd_git_diff_textconv(&file, show_values, identity_override)
+        }
+        Commands::MergeDriver { base, ours, theirs } => {
+            cmd_merge_driver(&base, &ours, &theirs, identity_override)
         }
-        MemberCommands::Role { command } => cmd_member_role(command, identity_override),
-        MemberCommands::Rm { name, yes } => cmd_member_rm(&name, yes, identity_override),
-        MemberCommands::Ls => cmd_member_ls(),
     }
 }
 
-fn cmd_member_role(command: MemberRoleCommands, identity_override: Option<&Path>) -> Result<()> {
-    match command {
-        MemberRoleCommands::Set { name, role } => {
-            cmd_member_role_set(&name, role.into(), identity_override)
+fn cmd_member(
+    envkey_path: &Path,
+    command: MemberCommands,
+    identity_override: &[PathBuf],
+    output: OutputFormat,
+) -> Result<()> {
+    let report = match command {
+        MemberCommands::Add { name, pubkey, role, envs } => cmd_member_add(
+            envkey_path,
+            &name,
+            pubkey.as_deref(),
+            role.into(),
+            envs,
+            identity_override,
+        )?,
+        MemberCommands::Update { name, pubkey } => {
+            cmd_member_update(envkey_path, &name, &pubkey, identity_override)?
         }
-    }
+        MemberCommands::Role { command: MemberRoleCommands::Set { name, role } } => {
+            cmd_member_role_set(envkey_path, &name, role.into(), identity_override)?
+        }
+        MemberCommands::Rm { name, yes } => {
+            if !yes && !output.is_table() {
+                return Err(EnvkeyError::message(
+                    "member rm needs --yes when printing structured output",
+                ));
+            }
+            cmd_member_rm(envkey_path, &name, yes, identity_override)?
+        }
+        MemberCommands::Grant { name, envs } => {
+            cmd_member_grant(envkey_path, &name, &envs, identity_override)?
+        }
+        MemberCommands::Revoke { name, envs } => {
+            cmd_member_revoke(envkey_path, &name, &envs, identity_override)?
+        }
+        MemberCommands::Ls => return cmd_member_ls(envkey_path, output),
+    };
+
+    output.print(&report, || print_member_report(&report))
 }
 
-fn cmd_init(force: bool, identity_override: Option<&Path>) -> Result<()> {
-    let cwd = env::current_dir()?;
-    let envkey_path = envkey_path(&cwd);
+fn cmd_init(
+    envkey_path: &Path,
+    force: bool,
+    passphrase: bool,
+    identity_override: &[PathBuf],
+    output: OutputFormat,
+) -> Result<()> {
     let identity_path = resolve_init_identity_path(identity_override)?;
-    let (bundle, generated_identity) = load_or_generate_identity(&identity_path, force)?;
+    let passphrase = if passphrase {
+        if !force && identity_exists(&identity_path) {
+            return Err(EnvkeyError::message(format!(
+                "identity already exists at {}; protect it with `envkey identity passwd`",
+                identity_path.display()
+            )));
+        }
+        Some(new_passphrase()?)
+    } else {
+        None
+    };
+    let (bundle, generated_identity) =
+        load_or_generate_identity(&identity_path, force, passphrase.as_ref())?;
     let mut created_envkey = false;
 
-    with_envkey_lock(&envkey_path, || {
+    with_envkey_lock(envkey_path, || {
         if force && envkey_path.exists() {
             return Err(EnvkeyError::message(
                 "--force is blocked when .envkey already exists; remove .envkey first in M1",
@@ -166,177 +557,1651 @@ fn cmd_init(force: bool, identity_override: Option<&Path>) -> Result<()> {
 
         if !envkey_path.exists() {
             let username = detect_username();
-            let file = EnvkeyFile::new(username, bundle.recipient.to_string(), now_date());
-            write_envkey_atomic(&envkey_path, &file)?;
+            let signing_key = bundle.signing_key()?;
+            let mut file =
+                EnvkeyFile::new(username.clone(), signing_key.to_public().to_string(), now_date());
+            sign_member(&mut file, &username, &username, signing_key)?;
+            seal_roster(&mut file, &username, signing_key)?;
+            write_envkey_atomic(envkey_path, &file)?;
             created_envkey = true;
         }
 
         Ok(())
     })?;
 
-    if generated_identity {
-        println!("✓ Generated identity key at {}", bundle.path.display());
-    } else {
-        println!("✓ Using existing identity key at {}", bundle.path.display());
-    }
+    let report = InitReport {
+        identity: bundle.path.display().to_string(),
+        identity_generated: generated_identity,
+        passphrase_protected: bundle.passphrase_protected,
+        envkey: envkey_path.display().to_string(),
+        envkey_created: created_envkey,
+        public_key: bundle.recipient().to_string(),
+    };
+    output.print(&report, || {
+        if generated_identity && bundle.passphrase_protected {
+            println!("✓ Generated passphrase-protected identity key at {}", bundle.path.display());
+        } else if generated_identity {
+            println!("✓ Generated identity key at {}", bundle.path.display());
+        } else {
+            println!("✓ Using existing identity key at {}", bundle.path.display());
+        }
 
-    if created_envkey {
-        println!("✓ Created .envkey with you as admin");
-    } else {
-        println!("✓ .envkey already exists");
-    }
+        if created_envkey {
+            println!("✓ Created .envkey with you as admin");
+        } else {
+            println!("✓ .envkey already exists");
+        }
 
-    println!("✓ Public key: {}", bundle.recipient);
-    Ok(())
+        println!("✓ Public key: {}", bundle.recipient());
+    })
 }
 
 fn cmd_set(
+    envkey_path: &Path,
     env_name: &str,
     key: &str,
-    value: String,
-    identity_override: Option<&Path>,
+    secret: SecretSlice<u8>,
+    encoding: SecretEncoding,
+    identity_override: &[PathBuf],
+    output: OutputFormat,
 ) -> Result<()> {
-    require_m1_env(env_name)?;
+    validate_environment_name(env_name)?;
     validate_secret_key(key)?;
 
-    let cwd = env::current_dir()?;
-    let envkey_path = envkey_path(&cwd);
-    let identity_bundle = load_identity_from(&resolve_identity_path(identity_override)?)?;
-    let secret: SecretString = value.into();
+    let identity_bundle = load_identities(identity_override)?;
     let mut recipient_count = 0usize;
+    let set_by = detect_username();
+    let modified = now_timestamp();
 
-    with_envkey_lock(&envkey_path, || {
+    with_envkey_lock(envkey_path, || {
         if !envkey_path.exists() {
-            return Err(EnvkeyError::message(
-                "missing .envkey in current directory; run `envkey init` first",
-            ));
+            return Err(missing_envkey(envkey_path));
+        }
+
+        let mut file = read_trusted_envkey(envkey_path)?;
+        let data_key = data_key_for_write(&mut file, env_name, identity_bundle.identity.as_ref())?;
+        let encrypted = data_key.seal(key, secret.expose_secret())?;
+        recipient_count = parse_recipients_for_env(&file, env_name)?.len();
+
+        file.env_mut(env_name).insert(
+            key.to_string(),
+            SecretEntry {
+                value: encrypted,
+                encoding: (encoding == SecretEncoding::Binary).then_some(encoding),
+                set_by: set_by.clone(),
+                modified: modified.clone(),
+            },
+        );
+
+        write_envkey_atomic(envkey_path, &file)?;
+        Ok(())
+    })?;
+
+    let report = SetReport {
+        secret: SecretInfo {
+            environment: env_name.to_string(),
+            key: key.to_string(),
+            encoding,
+            set_by,
+            modified,
+        },
+        recipients: recipient_count,
+    };
+    output.print(&report, || {
+        println!(
+            "✓ Encrypted {} for {} recipient{} ({})",
+            key,
+            recipient_count,
+            if recipient_count == 1 { "" } else { "s" },
+            env_name
+        );
+    })
+}
+
+/// Resolves the plaintext for `envkey set` without ever requiring it on the command line.
+///
+/// File and stdin input that is not valid UTF-8 is stored as binary automatically.
+fn read_secret_value(
+    key: &str,
+    value: Option<String>,
+    from_file: Option<&Path>,
+    force_binary: bool,
+) -> Result<(SecretSlice<u8>, SecretEncoding)> {
+    let (mut bytes, strip_newline) = if let Some(path) = from_file {
+        let raw = fs::read(path).map_err(|err| {
+            EnvkeyError::message(format!("failed to read {}: {err}", path.display()))
+        })?;
+        (raw, false)
+    } else {
+        match value {
+            Some(value) if value == "-" => {
+                let mut buffer = Vec::new();
+                io::stdin().read_to_end(&mut buffer)?;
+                (buffer, true)
+            }
+            Some(value) => (value.into_bytes(), false),
+            None if io::stdin().is_terminal() => {
+                let entered = rpassword::prompt_password(format!("Value for {key}: "))?;
+                (entered.into_bytes(), false)
+            }
+            None => {
+                return Err(EnvkeyError::message(format!(
+                    "missing value for {key}; pass it as an argument, `-` to read stdin, or --from-file PATH"
+                )));
+            }
+        }
+    };
+
+    let encoding = if force_binary || std::str::from_utf8(&bytes).is_err() {
+        SecretEncoding::Binary
+    } else {
+        SecretEncoding::Utf8
+    };
+
+    // Drop the newline that `echo` and most pipelines append to text on stdin.
+    if strip_newline && encoding == SecretEncoding::Utf8 && bytes.ends_with(b"\n") {
+        bytes.pop();
+        if bytes.ends_with(b"\r") {
+            bytes.pop();
+        }
+    }
+
+    Ok((bytes.into(), encoding))
+}
+
+fn cmd_get(
+    envkey_path: &Path,
+    env_name: &str,
+    key: &str,
+    raw: bool,
+    output_file: Option<&Path>,
+    identity_override: &[PathBuf],
+    output: OutputFormat,
+) -> Result<()> {
+    validate_environment_name(env_name)?;
+    if raw && !output.is_table() {
+        return Err(EnvkeyError::message(
+            "--raw prints the stored bytes and cannot be combined with structured output",
+        ));
+    }
+
+    if !envkey_path.exists() {
+        return Err(missing_envkey(envkey_path));
+    }
+
+    let file = read_trusted_envkey(envkey_path)?;
+    let identity = load_identities(identity_override)?;
+
+    let env = file
+        .env(env_name)
+        .ok_or_else(|| EnvkeyError::message(format!("environment not found: {env_name}")))?;
+    let entry =
+        env.get(key).ok_or_else(|| EnvkeyError::message(format!("secret key not found: {key}")))?;
+    let decryptor = EnvDecryptor::new(&file, env_name, identity.identity.as_ref())?;
+    let info = SecretInfo {
+        environment: env_name.to_string(),
+        key: key.to_string(),
+        encoding: entry.encoding(),
+        set_by: entry.set_by.clone(),
+        modified: entry.modified.clone(),
+    };
+
+    if let Some(path) = output_file {
+        let plaintext = decryptor.decrypt(key, &entry.value)?;
+        write_private_file(path, plaintext.expose_secret()).map_err(|err| {
+            EnvkeyError::message(format!("failed to write {}: {err}", path.display()))
+        })?;
+        let report =
+            GetReport { secret: info, value: None, written_to: Some(path.display().to_string()) };
+        return output.print(&report, || println!("✓ Wrote {} to {}", key, path.display()));
+    }
+
+    if raw {
+        let plaintext = decryptor.decrypt(key, &entry.value)?;
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(plaintext.expose_secret())?;
+        stdout.flush()?;
+        return Ok(());
+    }
+
+    if entry.encoding() == SecretEncoding::Binary && output.is_table() {
+        return Err(EnvkeyError::message(format!(
+            "secret {key} is binary; use --raw or --output FILE to read it"
+        )));
+    }
+
+    let plaintext = decrypt_entry_text(&decryptor, key, entry)?;
+    let report = GetReport {
+        secret: info,
+        value: Some(plaintext.expose_secret().to_string()),
+        written_to: None,
+    };
+    output.print(&report, || println!("{}", plaintext.expose_secret()))
+}
+
+/// Decrypts a value for text-only consumers; binary values are base64-encoded.
+fn decrypt_entry_text(
+    decryptor: &EnvDecryptor,
+    key: &str,
+    entry: &SecretEntry,
+) -> Result<SecretString> {
+    let plaintext = decryptor.decrypt(key, &entry.value)?;
+    match entry.encoding() {
+        SecretEncoding::Utf8 => std::str::from_utf8(plaintext.expose_secret())
+            .map(|text| text.to_string().into())
+            .map_err(|err| {
+                EnvkeyError::message(format!("decrypted value is not valid UTF-8: {err}"))
+            }),
+        SecretEncoding::Binary => Ok(STANDARD.encode(plaintext.expose_secret()).into()),
+    }
+}
+
+fn cmd_run(
+    envkey_path: &Path,
+    env_name: &str,
+    only: &[String],
+    exclude: &[String],
+    override_existing: bool,
+    command: &[String],
+    identity_override: &[PathBuf],
+) -> Result<()> {
+    validate_environment_name(env_name)?;
+
+    if !envkey_path.exists() {
+        return Err(missing_envkey(envkey_path));
+    }
+
+    let file = read_trusted_envkey(envkey_path)?;
+    let identity = load_identities(identity_override)?;
+    let env = file
+        .env(env_name)
+        .ok_or_else(|| EnvkeyError::message(format!("environment not found: {env_name}")))?;
+
+    for key in only.iter().chain(exclude) {
+        if !env.contains_key(key) {
+            return Err(EnvkeyError::message(format!("secret key not found: {key}")));
+        }
+    }
+
+    let selected: Vec<&String> = env
+        .keys()
+        .filter(|key| only.is_empty() || only.contains(key))
+        .filter(|key| !exclude.contains(key))
+        .collect();
+
+    if !override_existing {
+        let clobbered: Vec<&str> = selected
+            .iter()
+            .filter(|key| env::var_os(key.as_str()).is_some())
+            .map(|key| key.as_str())
+            .collect();
+        if !clobbered.is_empty() {
+            return Err(EnvkeyError::message(format!(
+                "refusing to override existing environment variable{} {}; pass --override to replace",
+                if clobbered.len() == 1 { "" } else { "s" },
+                clobbered.join(", ")
+            )));
+        }
+    }
+
+    let (program, args) =
+        command.split_first().ok_or_else(|| EnvkeyError::message("missing command to run"))?;
+    let decryptor = EnvDecryptor::new(&file, env_name, identity.identity.as_ref())?;
+    let mut child = Command::new(program);
+    child.args(args);
+    for key in selected {
+        let plaintext = decrypt_entry_text(&decryptor, key, &env[key])?;
+        child.env(key, plaintext.expose_secret());
+    }
+
+    exec_command(child, program)
+}
+
+/// Replaces the envkey process so the command keeps its pid, receives signals
+/// directly and its exit status becomes ours.
+#[cfg(unix)]
+fn exec_command(mut command: Command, program: &str) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    let err = command.exec();
+    Err(EnvkeyError::message(format!("failed to run {program}: {err}")))
+}
+
+#[cfg(not(unix))]
+fn exec_command(mut command: Command, program: &str) -> Result<()> {
+    let status = command
+        .status()
+        .map_err(|err| EnvkeyError::message(format!("failed to run {program}: {err}")))?;
+    std::process::exit(status.code().unwrap_or(1));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImportPolicy {
+    Reject,
+    SkipExisting,
+    Overwrite,
+}
+
+/// Conflict policy and dry-run switch shared by `import` and `promote`.
+#[derive(Debug, Clone, Copy)]
+struct ImportOptions {
+    policy: ImportPolicy,
+    dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImportStatus {
+    Added,
+    Changed,
+    Unchanged,
+    Skipped,
+}
+
+impl ImportStatus {
+    fn label(self) -> &'static str {
+        match self {
+            ImportStatus::Added => "added",
+            ImportStatus::Changed => "changed",
+            ImportStatus::Unchanged => "unchanged",
+            ImportStatus::Skipped => "skipped",
+        }
+    }
+}
+
+fn cmd_import(
+    envkey_path: &Path,
+    env_name: &str,
+    path: &Path,
+    options: ImportOptions,
+    identity_override: &[PathBuf],
+    output: OutputFormat,
+) -> Result<()> {
+    validate_environment_name(env_name)?;
+    let ImportOptions { policy, dry_run } = options;
+
+    let raw = fs::read_to_string(path)
+        .map_err(|err| EnvkeyError::message(format!("failed to read {}: {err}", path.display())))?;
+    let mut entries: BTreeMap<String, SecretString> = BTreeMap::new();
+    for (key, value) in dotenv::parse(&raw)? {
+        validate_secret_key(&key)?;
+        entries.insert(key, value.into());
+    }
+
+    let identity_bundle = load_identities(identity_override)?;
+    let mut statuses: Vec<(ImportStatus, &str)> = Vec::new();
+
+    with_envkey_lock(envkey_path, || {
+        if !envkey_path.exists() {
+            return Err(missing_envkey(envkey_path));
+        }
+
+        let mut file = read_trusted_envkey(envkey_path)?;
+        let decryptor = EnvDecryptor::new(&file, env_name, identity_bundle.identity.as_ref())?;
+        let mut to_write = Vec::new();
+        let mut conflicts = Vec::new();
+        for (key, value) in &entries {
+            let status = match file.env(env_name).and_then(|env| env.get(key)) {
+                None => ImportStatus::Added,
+                Some(existing) => {
+                    let current = decryptor.decrypt(key, &existing.value)?;
+                    if current.expose_secret() == value.expose_secret().as_bytes() {
+                        ImportStatus::Unchanged
+                    } else {
+                        match policy {
+                            ImportPolicy::Reject => {
+                                conflicts.push(key.as_str());
+                                ImportStatus::Changed
+                            }
+                            ImportPolicy::SkipExisting => ImportStatus::Skipped,
+                            ImportPolicy::Overwrite => ImportStatus::Changed,
+                        }
+                    }
+                }
+            };
+            if matches!(status, ImportStatus::Added | ImportStatus::Changed) {
+                to_write.push(key);
+            }
+            statuses.push((status, key.as_str()));
+        }
+
+        if !conflicts.is_empty() {
+            return Err(EnvkeyError::message(format!(
+                "{} already set with different values in {env_name}: {}; pass --overwrite or --skip-existing",
+                if conflicts.len() == 1 { "key is" } else { "keys are" },
+                conflicts.join(", ")
+            )));
+        }
+        if dry_run || to_write.is_empty() {
+            return Ok(());
+        }
+
+        let data_key = data_key_for_write(&mut file, env_name, identity_bundle.identity.as_ref())?;
+        let set_by = detect_username();
+        let modified = now_timestamp();
+        for key in to_write {
+            let encrypted = data_key.seal(key, entries[key].expose_secret().as_bytes())?;
+            file.env_mut(env_name).insert(
+                key.clone(),
+                SecretEntry {
+                    value: encrypted,
+                    encoding: None,
+                    set_by: set_by.clone(),
+                    modified: modified.clone(),
+                },
+            );
         }
 
-        let mut file = read_envkey(&envkey_path)?;
-        let recipients = parse_recipients_from_team(&file)?;
-        if recipients.is_empty() {
+        write_envkey_atomic(envkey_path, &file)?;
+        Ok(())
+    })?;
+
+    let count =
+        |wanted: ImportStatus| statuses.iter().filter(|(status, _)| *status == wanted).count();
+    let summary = format!(
+        "{} added, {} changed, {} unchanged, {} skipped",
+        count(ImportStatus::Added),
+        count(ImportStatus::Changed),
+        count(ImportStatus::Unchanged),
+        count(ImportStatus::Skipped)
+    );
+    let report = ImportReport {
+        environment: env_name.to_string(),
+        path: path.display().to_string(),
+        dry_run,
+        changes: key_changes(statuses.iter().map(|(status, key)| (status.label(), *key))),
+    };
+    output.print(&report, || {
+        if dry_run {
+            for (status, key) in &statuses {
+                println!("  {:<9}  {}", status.label(), key);
+            }
+            println!("✓ Dry run for {env_name}: {summary} (nothing written)");
+        } else {
+            println!("✓ Imported {} into {env_name}: {summary}", path.display());
+        }
+    })
+}
+
+fn key_changes<'a>(changes: impl Iterator<Item = (&'static str, &'a str)>) -> Vec<KeyChange> {
+    changes.map(|(status, key)| KeyChange { key: key.to_string(), status }).collect()
+}
+
+fn cmd_promote(
+    envkey_path: &Path,
+    from: &str,
+    to: &str,
+    keys: &[String],
+    options: ImportOptions,
+    identity_override: &[PathBuf],
+    output: OutputFormat,
+) -> Result<()> {
+    let ImportOptions { policy, dry_run } = options;
+    validate_environment_name(from)?;
+    validate_environment_name(to)?;
+    for key in keys {
+        validate_secret_key(key)?;
+    }
+    if from == to {
+        return Err(EnvkeyError::message("source and destination environments are the same"));
+    }
+
+    let identity_bundle = load_identities(identity_override)?;
+    let mut statuses: Vec<(ImportStatus, String)> = Vec::new();
+
+    with_envkey_lock(envkey_path, || {
+        if !envkey_path.exists() {
+            return Err(missing_envkey(envkey_path));
+        }
+
+        let mut file = read_trusted_envkey(envkey_path)?;
+        let source = file
+            .env(from)
+            .ok_or_else(|| EnvkeyError::message(format!("environment not found: {from}")))?;
+        let selected: Vec<String> = if keys.is_empty() {
+            source.keys().cloned().collect()
+        } else {
+            let missing: Vec<&str> =
+                keys.iter().filter(|key| !source.contains_key(*key)).map(String::as_str).collect();
+            if !missing.is_empty() {
+                return Err(EnvkeyError::message(format!(
+                    "secret key not found in {from}: {}",
+                    missing.join(", ")
+                )));
+            }
+            keys.to_vec()
+        };
+
+        let source_decryptor = EnvDecryptor::new(&file, from, identity_bundle.identity.as_ref())?;
+        let dest_decryptor = EnvDecryptor::new(&file, to, identity_bundle.identity.as_ref())?;
+        let mut to_write = Vec::new();
+        let mut conflicts = Vec::new();
+        for key in &selected {
+            let entry = &source[key];
+            let plaintext = source_decryptor.decrypt(key, &entry.value)?;
+            let status = match file.env(to).and_then(|env| env.get(key)) {
+                None => ImportStatus::Added,
+                Some(existing) => {
+                    let current = dest_decryptor.decrypt(key, &existing.value)?;
+                    if current.expose_secret() == plaintext.expose_secret()
+                        && existing.encoding() == entry.encoding()
+                    {
+                        ImportStatus::Unchanged
+                    } else {
+                        if policy == ImportPolicy::Reject {
+                            conflicts.push(key.as_str());
+                        }
+                        ImportStatus::Changed
+                    }
+                }
+            };
+            if status != ImportStatus::Unchanged {
+                to_write.push((key.clone(), entry.encoding(), plaintext));
+            }
+            statuses.push((status, key.clone()));
+        }
+
+        if !conflicts.is_empty() {
+            return Err(EnvkeyError::message(format!(
+                "{} already set with different values in {to}: {}; pass --overwrite to replace them",
+                if conflicts.len() == 1 { "key is" } else { "keys are" },
+                conflicts.join(", ")
+            )));
+        }
+        if dry_run || to_write.is_empty() {
+            return Ok(());
+        }
+
+        let data_key = data_key_for_write(&mut file, to, identity_bundle.identity.as_ref())?;
+        let set_by = detect_username();
+        let modified = now_timestamp();
+        for (key, encoding, plaintext) in to_write {
+            let encrypted = data_key.seal(&key, plaintext.expose_secret())?;
+            file.env_mut(to).insert(
+                key,
+                SecretEntry {
+                    value: encrypted,
+                    encoding: (encoding == SecretEncoding::Binary).then_some(encoding),
+                    set_by: set_by.clone(),
+                    modified: modified.clone(),
+                },
+            );
+        }
+
+        write_envkey_atomic(envkey_path, &file)?;
+        Ok(())
+    })?;
+
+    let count =
+        |wanted: ImportStatus| statuses.iter().filter(|(status, _)| *status == wanted).count();
+    let summary = format!(
+        "{} added, {} changed, {} unchanged",
+        count(ImportStatus::Added),
+        count(ImportStatus::Changed),
+        count(ImportStatus::Unchanged)
+    );
+    let report = PromoteReport {
+        from: from.to_string(),
+        to: to.to_string(),
+        dry_run,
+        changes: key_changes(statuses.iter().map(|(status, key)| (status.label(), key.as_str()))),
+    };
+    output.print(&report, || {
+        for (status, key) in &statuses {
+            println!("  {:<9}  {}", status.label(), key);
+        }
+        if dry_run {
+            println!("✓ Dry run for {from} -> {to}: {summary} (nothing written)");
+        } else {
+            println!("✓ Promoted {from} -> {to}: {summary}");
+        }
+    })
+}
+
+fn cmd_export(
+    envkey_path: &Path,
+    env_name: &str,
+    format: ExportFormat,
+    output_file: Option<&Path>,
+    identity_override: &[PathBuf],
+    output: OutputFormat,
+) -> Result<()> {
+    validate_environment_name(env_name)?;
+
+    if !envkey_path.exists() {
+        return Err(missing_envkey(envkey_path));
+    }
+
+    let file = read_trusted_envkey(envkey_path)?;
+    let identity = load_identities(identity_override)?;
+    let env = file
+        .env(env_name)
+        .ok_or_else(|| EnvkeyError::message(format!("environment not found: {env_name}")))?;
+
+    let decryptor = EnvDecryptor::new(&file, env_name, identity.identity.as_ref())?;
+    let mut secrets = BTreeMap::new();
+    for (key, entry) in env {
+        let plaintext = decrypt_entry_text(&decryptor, key, entry)?;
+        secrets.insert(key.clone(), plaintext);
+    }
+    let rendered: SecretString = render_export(format, &secrets)?.into();
+
+    match output_file {
+        Some(path) => {
+            write_private_file(path, rendered.expose_secret().as_bytes()).map_err(|err| {
+                EnvkeyError::message(format!("failed to write {}: {err}", path.display()))
+            })?;
+            let report = ExportReport {
+                environment: env_name.to_string(),
+                secrets: secrets.len(),
+                written_to: path.display().to_string(),
+            };
+            output.print(&report, || {
+                println!(
+                    "✓ Exported {} secret{} from {} to {}",
+                    secrets.len(),
+                    if secrets.len() == 1 { "" } else { "s" },
+                    env_name,
+                    path.display()
+                );
+            })?;
+        }
+        None => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(rendered.expose_secret().as_bytes())?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+enum DiffTarget {
+    /// Two environments of the same file.
+    Environments,
+    Revision(String),
+    File(PathBuf),
+}
+
+/// Decrypted secrets of one environment, keyed by name.
+type PlainEnv = BTreeMap<String, (SecretEncoding, SecretSlice<u8>)>;
+
+fn cmd_diff(
+    envkey_path: &Path,
+    envs: &[String],
+    target: DiffTarget,
+    show_values: bool,
+    identity_override: &[PathBuf],
+    output: OutputFormat,
+) -> Result<()> {
+    for env_name in envs {
+        validate_environment_name(env_name)?;
+    }
+    if !envkey_path.exists() {
+        return Err(missing_envkey(envkey_path));
+    }
+
+    let identity = load_identities(identity_override)?;
+    let current = read_trusted_envkey(envkey_path)?;
+
+    let (other, other_label) = match &target {
+        DiffTarget::Environments => {
+            let [left, right] = envs else {
+                return Err(EnvkeyError::message(
+                    "envkey diff needs two environments, or --rev / --against",
+                ));
+            };
+            for env_name in [left, right] {
+                if current.env(env_name).is_none() {
+                    return Err(EnvkeyError::message(format!("environment not found: {env_name}")));
+                }
+            }
+            let old = decrypt_env_for_diff(&current, left, identity.identity.as_ref())?;
+            let new = decrypt_env_for_diff(&current, right, identity.identity.as_ref())?;
+            let comparison = diff_env(None, left, right, &old, &new, show_values);
+            let report = DiffReport { comparisons: vec![comparison], not_readable: Vec::new() };
+            return output.print(&report, || print_diff(&report));
+        }
+        DiffTarget::Revision(rev) => {
+            let raw = git::show_at_revision(envkey_path, rev)?;
+            let label = format!("{rev}:{}", envkey_path.display());
+            (parse_envkey(&raw, &label)?, rev.clone())
+        }
+        DiffTarget::File(path) => (read_envkey(path)?, path.display().to_string()),
+    };
+
+    let env_names: Vec<String> = match envs {
+        [] => other
+            .environments
+            .keys()
+            .chain(current.environments.keys())
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect(),
+        [env_name] => {
+            if other.env(env_name).is_none() && current.env(env_name).is_none() {
+                return Err(EnvkeyError::message(format!("environment not found: {env_name}")));
+            }
+            vec![env_name.clone()]
+        }
+        _ => {
             return Err(EnvkeyError::message(
-                "no team recipients found in .envkey; cannot encrypt",
+                "envkey diff compares one environment at a time with --rev or --against",
             ));
         }
+    };
+
+    let mut comparisons = Vec::new();
+    let mut not_readable = Vec::new();
+    for env_name in env_names {
+        let decrypt = |file| decrypt_env_for_diff(file, &env_name, identity.identity.as_ref());
+        let (old, new) = match decrypt(&other).and_then(|old| Ok((old, decrypt(&current)?))) {
+            Ok(sides) => sides,
+            // Without an environment argument the diff covers what this identity can read,
+            // which excludes restricted environments and revisions from before it joined.
+            Err(_) if envs.is_empty() => {
+                not_readable.push(env_name);
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        comparisons.push(diff_env(
+            Some(&env_name),
+            &other_label,
+            "working copy",
+            &old,
+            &new,
+            show_values,
+        ));
+    }
+    let report = DiffReport { comparisons, not_readable };
+    output.print(&report, || print_diff(&report))
+}
+
+fn decrypt_env_for_diff(
+    file: &EnvkeyFile,
+    env_name: &str,
+    identity: &dyn age::Identity,
+) -> Result<PlainEnv> {
+    let Some(env) = file.env(env_name) else {
+        return Ok(PlainEnv::new());
+    };
+    let decryptor = EnvDecryptor::new(file, env_name, identity)?;
+    env.iter()
+        .map(|(key, entry)| {
+            let plaintext = decryptor.decrypt(key, &entry.value)?;
+            Ok((key.clone(), (entry.encoding(), plaintext)))
+        })
+        .collect()
+}
+
+fn diff_env(
+    env_name: Option<&str>,
+    from: &str,
+    to: &str,
+    old: &PlainEnv,
+    new: &PlainEnv,
+    show_values: bool,
+) -> DiffComparison {
+    let shown = |side: Option<&(SecretEncoding, SecretSlice<u8>)>| {
+        side.filter(|_| show_values).map(|(encoding, value)| {
+            let bytes = value.expose_secret();
+            match (encoding, std::str::from_utf8(bytes)) {
+                (SecretEncoding::Utf8, Ok(text)) => {
+                    DiffValue { value: text.to_string(), encoding: SecretEncoding::Utf8 }
+                }
+                _ => DiffValue { value: STANDARD.encode(bytes), encoding: SecretEncoding::Binary },
+            }
+        })
+    };
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    let changes = keys
+        .into_iter()
+        .filter_map(|key| {
+            let (old_side, new_side) = (old.get(key), new.get(key));
+            let status = match (old_side, new_side) {
+                (None, Some(_)) => "added",
+                (Some(_), None) => "removed",
+                (Some((old_encoding, old_value)), Some((new_encoding, new_value))) => {
+                    if old_encoding == new_encoding
+                        && old_value.expose_secret() == new_value.expose_secret()
+                    {
+                        return None;
+                    }
+                    "changed"
+                }
+                (None, None) => return None,
+            };
+            Some(DiffEntry { key: key.clone(), status, old: shown(old_side), new: shown(new_side) })
+        })
+        .collect();
+
+    DiffComparison {
+        environment: env_name.map(str::to_string),
+        from: from.to_string(),
+        to: to.to_string(),
+        changes,
+    }
+}
+
+fn print_diff(report: &DiffReport) {
+    for comparison in &report.comparisons {
+        match &comparison.environment {
+            Some(env_name) => println!("{env_name}: {} -> {}", comparison.from, comparison.to),
+            None => println!("{} -> {}", comparison.from, comparison.to),
+        }
+        let shown = |value: &DiffValue| match value.encoding {
+            SecretEncoding::Utf8 => format!("\"{}\"", value.value.escape_debug()),
+            SecretEncoding::Binary => format!("base64:{}", value.value),
+        };
+        for change in &comparison.changes {
+            let key = &change.key;
+            let (old, new) = (change.old.as_ref().map(shown), change.new.as_ref().map(shown));
+            match (change.status, old, new) {
+                ("added", _, Some(value)) => println!("+ {key} = {value}"),
+                ("added", _, _) => println!("+ {key}"),
+                ("removed", Some(value), _) => println!("- {key} = {value}"),
+                ("removed", _, _) => println!("- {key}"),
+                (_, Some(old), Some(new)) => println!("~ {key}: {old} -> {new}"),
+                _ => println!("~ {key}"),
+            }
+        }
+        if comparison.changes.is_empty() {
+            println!("  no differences");
+        }
+    }
+    for env_name in &report.not_readable {
+        println!("{env_name}: not readable with your identity; skipped");
+    }
+}
+
+/// Decrypts the environment into a dotenv file in a private scratch directory, opens it
+/// in the user's editor and writes back only what changed. Binary values are left out of
+/// the file and kept as they are.
+fn cmd_edit(
+    envkey_path: &Path,
+    env_name: &str,
+    identity_override: &[PathBuf],
+    output: OutputFormat,
+) -> Result<()> {
+    validate_environment_name(env_name)?;
+
+    if !envkey_path.exists() {
+        return Err(missing_envkey(envkey_path));
+    }
+
+    let identity_bundle = load_identities(identity_override)?;
+    let file = read_trusted_envkey(envkey_path)?;
+    let snapshot = file.env(env_name).cloned().unwrap_or_default();
+    let decryptor = EnvDecryptor::new(&file, env_name, identity_bundle.identity.as_ref())?;
+
+    let mut before: BTreeMap<String, SecretString> = BTreeMap::new();
+    let mut binary_keys = Vec::new();
+    for (key, entry) in &snapshot {
+        if entry.encoding() == SecretEncoding::Binary {
+            binary_keys.push(key.as_str());
+        } else {
+            before.insert(key.clone(), decrypt_entry_text(&decryptor, key, entry)?);
+        }
+    }
+
+    let mut content = Zeroizing::new(format!(
+        "# envkey edit: {env_name} in {}\n# Removing a line deletes its key; comments are discarded.\n",
+        envkey_path.display()
+    ));
+    if !binary_keys.is_empty() {
+        content.push_str(&format!(
+            "# Binary values are not shown and stay unchanged: {}\n",
+            binary_keys.join(", ")
+        ));
+    }
+    for (key, value) in &before {
+        content.push_str(&format!("{key}={}\n", dotenv::quote(value.expose_secret())));
+    }
+
+    let scratch = PrivateDir::create()?;
+    let scratch_file = scratch.path().join(format!("{env_name}.env"));
+    write_private_file(&scratch_file, content.as_bytes())?;
+
+    // The scratch file is kept until it parses, so a typo does not throw the edit away.
+    let after = loop {
+        edit::open_editor(&scratch_file)?;
+        let edited = Zeroizing::new(fs::read_to_string(&scratch_file).map_err(|err| {
+            EnvkeyError::message(format!("failed to read {}: {err}", scratch_file.display()))
+        })?);
+        let err = match parse_edited(&edited) {
+            Ok(after) => break after,
+            Err(err) => err,
+        };
+        if !output.is_table() || !confirm_reopen_editor(&err)? {
+            return Err(EnvkeyError::message(format!("{err}; no changes written")));
+        }
+        let retry = Zeroizing::new(edit::with_error_comment(&edited, &err.to_string()));
+        write_private_file(&scratch_file, retry.as_bytes())?;
+    };
+    drop(scratch);
 
-        let encrypted = encrypt_value(secret.expose_secret(), &recipients)?;
-        let _ = decrypt_value(&encrypted, &identity_bundle.identity)?;
-        recipient_count = recipients.len();
+    let changes = edit::changes(&before, &after);
+    let report = EditReport {
+        environment: env_name.to_string(),
+        changes: key_changes(changes.iter().map(|(key, change)| (change.label(), key.as_str()))),
+    };
+    if changes.is_empty() {
+        return output.print(&report, || println!("No changes to {env_name}"));
+    }
+
+    with_envkey_lock(envkey_path, || {
+        let mut file = read_trusted_envkey(envkey_path)?;
+        if file.env(env_name).cloned().unwrap_or_default() != snapshot {
+            return Err(EnvkeyError::message(format!(
+                "{env_name} was changed by someone else while you were editing; no changes written, run `envkey edit -e {env_name}` again"
+            )));
+        }
 
+        let data_key = data_key_for_write(&mut file, env_name, identity_bundle.identity.as_ref())?;
         let set_by = detect_username();
-        file.default_env_mut().insert(
-            key.to_string(),
-            SecretEntry { value: encrypted, set_by, modified: now_timestamp() },
+        let modified = now_timestamp();
+        for (key, change) in &changes {
+            if *change == EditChange::Removed {
+                file.env_mut(env_name).remove(key);
+                continue;
+            }
+            let encrypted = data_key.seal(key, after[key].expose_secret().as_bytes())?;
+            file.env_mut(env_name).insert(
+                key.clone(),
+                SecretEntry {
+                    value: encrypted,
+                    encoding: None,
+                    set_by: set_by.clone(),
+                    modified: modified.clone(),
+                },
+            );
+        }
+
+        write_envkey_atomic(envkey_path, &file)?;
+        Ok(())
+    })?;
+
+    let count = |wanted: EditChange| changes.iter().filter(|(_, change)| *change == wanted).count();
+    output.print(&report, || {
+        for (key, change) in &changes {
+            println!("  {:<9}  {}", change.label(), key);
+        }
+        println!(
+            "✓ Updated {env_name}: {} added, {} changed, {} removed",
+            count(EditChange::Added),
+            count(EditChange::Changed),
+            count(EditChange::Removed)
+        );
+    })
+}
+
+/// Reads the values back from an edited scratch file.
+fn parse_edited(edited: &str) -> Result<BTreeMap<String, SecretString>> {
+    let mut after = BTreeMap::new();
+    for (key, value) in dotenv::parse(edited)? {
+        validate_secret_key(&key)?;
+        after.insert(key, value.into());
+    }
+    Ok(after)
+}
+
+/// Environments a key-level command applies to: the one given with `-e`, or with
+/// `--all-envs` every environment holding `key`.
+fn envs_with_key(file: &EnvkeyFile, env_name: Option<&str>, key: &str) -> Result<Vec<String>> {
+    match env_name {
+        Some(env_name) => {
+            let env = file.env(env_name).ok_or_else(|| {
+                EnvkeyError::message(format!("environment not found: {env_name}"))
+            })?;
+            if !env.contains_key(key) {
+                return Err(EnvkeyError::message(format!(
+                    "secret key not found in {env_name}: {key}"
+                )));
+            }
+            Ok(vec![env_name.to_string()])
+        }
+        None => {
+            let envs: Vec<String> = file
+                .environments
+                .iter()
+                .filter(|(_, env)| env.secrets.contains_key(key))
+                .map(|(env_name, _)| env_name.clone())
+                .collect();
+            if envs.is_empty() {
+                return Err(EnvkeyError::message(format!(
+                    "secret key not found in any environment: {key}"
+                )));
+            }
+            Ok(envs)
+        }
+    }
+}
+
+fn cmd_rm(
+    envkey_path: &Path,
+    env_name: Option<&str>,
+    keys: &[String],
+    output: OutputFormat,
+) -> Result<()> {
+    if let Some(env_name) = env_name {
+        validate_environment_name(env_name)?;
+    }
+
+    let mut removed: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    with_envkey_lock(envkey_path, || {
+        if !envkey_path.exists() {
+            return Err(missing_envkey(envkey_path));
+        }
+
+        let mut file = read_trusted_envkey(envkey_path)?;
+        for key in keys {
+            for env_name in envs_with_key(&file, env_name, key)? {
+                removed.entry(env_name).or_default().push(key);
+            }
+        }
+        for (env_name, keys) in &removed {
+            let env = file.env_mut(env_name);
+            for key in keys {
+                env.remove(*key);
+            }
+        }
+
+        write_envkey_atomic(envkey_path, &file)?;
+        Ok(())
+    })?;
+
+    let report = RmReport {
+        removed: removed
+            .iter()
+            .map(|(env_name, keys)| RemovedKeys {
+                environment: env_name.clone(),
+                keys: keys.iter().map(|key| key.to_string()).collect(),
+            })
+            .collect(),
+    };
+    output.print(&report, || {
+        for (env_name, keys) in &removed {
+            println!("✓ Removed {} from {env_name}", keys.join(", "));
+        }
+    })
+}
+
+/// Values are bound to their key name, so renaming decrypts and re-seals each one.
+fn cmd_mv(
+    envkey_path: &Path,
+    env_name: Option<&str>,
+    old: &str,
+    new: &str,
+    identity_override: &[PathBuf],
+    output: OutputFormat,
+) -> Result<()> {
+    if let Some(env_name) = env_name {
+        validate_environment_name(env_name)?;
+    }
+    validate_secret_key(new)?;
+    if old == new {
+        return Err(EnvkeyError::message(format!("{old} already has that name")));
+    }
+
+    let identity_bundle = load_identities(identity_override)?;
+    let mut renamed = Vec::new();
+
+    with_envkey_lock(envkey_path, || {
+        if !envkey_path.exists() {
+            return Err(missing_envkey(envkey_path));
+        }
+
+        let mut file = read_trusted_envkey(envkey_path)?;
+        renamed = envs_with_key(&file, env_name, old)?;
+        let taken: Vec<&str> = renamed
+            .iter()
+            .filter(|env_name| file.env(env_name).is_some_and(|env| env.contains_key(new)))
+            .map(String::as_str)
+            .collect();
+        if !taken.is_empty() {
+            return Err(EnvkeyError::message(format!(
+                "secret key {new} already exists in {}; remove it first",
+                taken.join(", ")
+            )));
+        }
+
+        let set_by = detect_username();
+        let modified = now_timestamp();
+        for env_name in &renamed {
+            let decryptor = EnvDecryptor::new(&file, env_name, identity_bundle.identity.as_ref())?;
+            let entry = file.env_mut(env_name).remove(old).expect("key exists");
+            let plaintext = decryptor.decrypt(old, &entry.value)?;
+            let data_key =
+                data_key_for_write(&mut file, env_name, identity_bundle.identity.as_ref())?;
+            let encrypted = data_key.seal(new, plaintext.expose_secret())?;
+            file.env_mut(env_name).insert(
+                new.to_string(),
+                SecretEntry {
+                    value: encrypted,
+                    encoding: entry.encoding,
+                    set_by: set_by.clone(),
+                    modified: modified.clone(),
+                },
+            );
+        }
+
+        write_envkey_atomic(envkey_path, &file)?;
+        Ok(())
+    })?;
+
+    let report = MvReport { old: old.to_string(), new: new.to_string(), environments: renamed };
+    output.print(&report, || {
+        println!("✓ Renamed {old} to {new} in {}", report.environments.join(", "));
+    })
+}
+
+fn cmd_ls(envkey_path: &Path, env_name: Option<&str>, output: OutputFormat) -> Result<()> {
+    if let Some(env_name) = env_name {
+        validate_environment_name(env_name)?;
+    }
+
+    if !envkey_path.exists() {
+        return Err(missing_envkey(envkey_path));
+    }
+
+    let file = read_trusted_envkey(envkey_path)?;
+    if let Some(env_name) = env_name
+        && file.env(env_name).is_none()
+    {
+        return Err(EnvkeyError::message(format!("environment not found: {env_name}")));
+    }
+
+    // Environments and their secrets are both sorted maps, so rows come out ordered.
+    let secrets: Vec<SecretInfo> = file
+        .environments
+        .iter()
+        .filter(|(name, _)| env_name.is_none_or(|selected| selected == name.as_str()))
+        .flat_map(|(name, env)| {
+            env.secrets.iter().map(move |(key, entry)| SecretInfo {
+                environment: name.clone(),
+                key: key.clone(),
+                encoding: entry.encoding(),
+                set_by: entry.set_by.clone(),
+                modified: entry.modified.clone(),
+            })
+        })
+        .collect();
+    if !output.is_table() {
+        return output.print(&LsReport { secrets }, || {});
+    }
+
+    let rows: Vec<(String, String, String, String)> = secrets
+        .into_iter()
+        .map(|secret| (secret.environment, secret.key, secret.set_by, secret.modified))
+        .collect();
+
+    let env_w = rows
+        .iter()
+        .map(|row| row.0.len())
+        .max()
+        .unwrap_or("ENVIRONMENT".len())
+        .max("ENVIRONMENT".len());
+    let key_w = rows.iter().map(|row| row.1.len()).max().unwrap_or("KEY".len()).max("KEY".len());
+    let set_by_w =
+        rows.iter().map(|row| row.2.len()).max().unwrap_or("SET_BY".len()).max("SET_BY".len());
+
+    println!("{:<env_w$}  {:<key_w$}  {:<set_by_w$}  MODIFIED", "ENVIRONMENT", "KEY", "SET_BY");
+
+    for (env_name, key, set_by, modified) in rows {
+        println!("{:<env_w$}  {:<key_w$}  {:<set_by_w$}  {}", env_name, key, set_by, modified);
+    }
+
+    Ok(())
+}
+
+fn cmd_ls_projects(cwd: &Path, output: OutputFormat) -> Result<()> {
+    let config = ProjectConfig::discover(cwd)?.ok_or_else(|| {
+        EnvkeyError::message(format!(
+            "--all-projects needs an {PROJECT_FILE_NAME} in {} or its parent directories",
+            cwd.display()
+        ))
+    })?;
+
+    let mut projects = Vec::new();
+    for (name, display_path, path) in config.projects() {
+        let mut project = ProjectInfo {
+            name: name.to_string(),
+            path: display_path.display().to_string(),
+            environments: None,
+            secrets: None,
+            members: None,
+        };
+        if path.exists() {
+            let file = read_trusted_envkey(&path)?;
+            project.environments = Some(file.environments.len());
+            project.secrets = Some(file.environments.values().map(|env| env.secrets.len()).sum());
+            project.members = Some(file.team.len());
+        }
+        projects.push(project);
+    }
+    if !output.is_table() {
+        return output.print(&ProjectsReport { projects }, || {});
+    }
+
+    let count = |value: Option<usize>| value.map_or("-".to_string(), |value| value.to_string());
+    let rows: Vec<(String, String, String, String, String)> = projects
+        .into_iter()
+        .map(|project| {
+            (
+                project.name,
+                project.path,
+                count(project.environments),
+                count(project.secrets),
+                count(project.members),
+            )
+        })
+        .collect();
+
+    let name_w =
+        rows.iter().map(|row| row.0.len()).max().unwrap_or("PROJECT".len()).max("PROJECT".len());
+    let path_w = rows.iter().map(|row| row.1.len()).max().unwrap_or("PATH".len()).max("PATH".len());
+
+    println!("{:<name_w$}  {:<path_w$}  ENVIRONMENTS  SECRETS  MEMBERS", "PROJECT", "PATH");
+
+    for (name, path, environments, secrets, members) in rows {
+        println!(
+            "{:<name_w$}  {:<path_w$}  {:<12}  {:<7}  {}",
+            name, path, environments, secrets, members
+        );
+    }
+
+    Ok(())
+}
+
+fn cmd_migrate(
+    envkey_path: &Path,
+    yes: bool,
+    identity_override: &[PathBuf],
+    output: OutputFormat,
+) -> Result<()> {
+    if !envkey_path.exists() {
+        return Err(missing_envkey(envkey_path));
+    }
+
+    let mut report = MigrateReport {
+        from_version: FORMAT_VERSION,
+        to_version: FORMAT_VERSION,
+        signed_members: Vec::new(),
+        reencrypted: Vec::new(),
+    };
+    let mut signed = Vec::new();
+    with_envkey_lock(envkey_path, || {
+        let mut file = read_trusted_envkey(envkey_path)?;
+        let Some(from) = file.migrated_from.take() else {
+            return Ok(());
+        };
+        report.from_version = from;
+
+        // Version 1 rosters were never signed, so an admin has to vouch for them as they
+        // are, after checking that nobody slipped into the team while it was unprotected.
+        if from < SIGNED_FORMAT_VERSION {
+            let identity_bundle = load_identities(identity_override)?;
+            let (admin, signing_key) =
+                require_admin_identity(&file, &identity_bundle).map_err(|_| {
+                    EnvkeyError::message(format!(
+                        "migrating to version {SIGNED_FORMAT_VERSION} signs the team roster; run \
+                         `envkey migrate` with an admin identity"
+                    ))
+                })?;
+            if !yes {
+                if !output.is_table() {
+                    return Err(EnvkeyError::message(
+                        "migrate needs --yes to sign the team roster when printing structured \
+                         output",
+                    ));
+                }
+                if !confirm_roster_signing(&file, &admin)? {
+                    return Err(EnvkeyError::message("aborted"));
+                }
+            }
+            reseal_roster(&mut file, &admin, signing_key)?;
+            signed = file
+                .team
+                .iter()
+                .map(|(name, member)| format!("{name} ({})", role_label(&member.role)))
+                .collect();
+            report.signed_members = file.team.keys().cloned().collect();
+
+            // Moving values under data keys means decrypting them, so the admin's identity
+            // has to read every environment that still has secrets.
+            let legacy: Vec<String> = file
+                .environments
+                .iter()
+                .filter(|(_, env)| env.data_key.is_none() && !env.secrets.is_empty())
+                .map(|(env_name, _)| env_name.clone())
+                .collect();
+            for env_name in legacy {
+                let count =
+                    rotate_data_key(&mut file, &env_name, identity_bundle.identity.as_ref())
+                        .map_err(|err| {
+                            EnvkeyError::message(format!(
+                                "migrating to version {SIGNED_FORMAT_VERSION} re-encrypts every \
+                                 environment; run `envkey migrate` with an identity that can \
+                                 read {env_name}: {err}"
+                            ))
+                        })?;
+                report.reencrypted.push(Reencrypted { environment: env_name, secrets: count });
+            }
+        }
+
+        write_envkey_atomic(envkey_path, &file)
+    })?;
+
+    output.print(&report, || {
+        if report.from_version == FORMAT_VERSION {
+            println!("✓ .envkey is already at format version {FORMAT_VERSION}");
+            return;
+        }
+        println!("✓ Migrated .envkey from version {} to {FORMAT_VERSION}", report.from_version);
+        if !signed.is_empty() {
+            println!("✓ Signed team roster: {}", signed.join(", "));
+        }
+        if !report.reencrypted.is_empty() {
+            let enveloped: Vec<String> = report
+                .reencrypted
+                .iter()
+                .map(|env| format!("{} ({})", env.environment, env.secrets))
+                .collect();
+            println!("✓ Re-encrypted under new data keys: {}", enveloped.join(", "));
+        }
+    })
+}
+
+fn cmd_trust(envkey_path: &Path, yes: bool, output: OutputFormat) -> Result<()> {
+    if !envkey_path.exists() {
+        return Err(missing_envkey(envkey_path));
+    }
+    if !yes && !output.is_table() {
+        return Err(EnvkeyError::message("trust needs --yes when printing structured output"));
+    }
+
+    let file = read_envkey(envkey_path)?;
+    let Some(root) = file.team.iter().find(|(name, member)| member.signed_by == **name) else {
+        return Err(EnvkeyError::message(
+            "the team roster is not signed yet; run `envkey migrate` first",
+        ));
+    };
+    let known = KnownRoster::of(&file);
+    let report = TrustReport {
+        envkey: envkey_path.display().to_string(),
+        root: root.0.clone(),
+        admins: known.admins.keys().cloned().collect(),
+    };
+    if !yes && !confirm_roster_trust(&known, &report.root)? {
+        return Err(EnvkeyError::message("aborted"));
+    }
+    trust_roster(envkey_path, &file)?;
+
+    output.print(&report, || {
+        println!("✓ Trusted the team roster of {}", report.envkey);
+        println!("  Root admin: {}; admins: {}", report.root, report.admins.join(", "));
+    })
+}
+
+fn cmd_rotate(
+    envkey_path: &Path,
+    env_name: &str,
+    identity_override: &[PathBuf],
+    output: OutputFormat,
+) -> Result<()> {
+    validate_environment_name(env_name)?;
+
+    let identity_bundle = load_identities(identity_override)?;
+    let mut count = 0usize;
+    let mut recipients = 0usize;
+
+    with_envkey_lock(envkey_path, || {
+        if !envkey_path.exists() {
+            return Err(missing_envkey(envkey_path));
+        }
+
+        let mut file = read_trusted_envkey(envkey_path)?;
+        require_admin_identity(&file, &identity_bundle)?;
+        if file.env(env_name).is_none() {
+            return Err(EnvkeyError::message(format!("environment not found: {env_name}")));
+        }
+
+        count = rotate_data_key(&mut file, env_name, identity_bundle.identity.as_ref())?;
+        recipients = parse_recipients_for_env(&file, env_name)?.len();
+        write_envkey_atomic(envkey_path, &file)?;
+        Ok(())
+    })?;
+
+    let report = RotateReport {
+        reencrypted: Reencrypted { environment: env_name.to_string(), secrets: count },
+        recipients,
+    };
+    output.print(&report, || {
+        println!(
+            "✓ Rotated data key for {} — re-encrypted {} secret{}",
+            env_name,
+            count,
+            if count == 1 { "" } else { "s" }
         );
+    })
+}
 
-        write_envkey_atomic(&envkey_path, &file)?;
-        Ok(())
-    })?;
+fn cmd_identity_passwd(
+    remove: bool,
+    identity_override: &[PathBuf],
+    output: OutputFormat,
+) -> Result<()> {
+    let identity_file = IdentityFile::read(&resolve_identity_path(identity_override)?)?;
+    let bundle = identity_file.keys()?;
+    if remove && !bundle.passphrase_protected {
+        return Err(EnvkeyError::message(format!(
+            "identity at {} is not passphrase-protected",
+            bundle.path.display()
+        )));
+    }
 
-    println!(
-        "✓ Encrypted {} for {} recipient{} ({})",
-        key,
-        recipient_count,
-        if recipient_count == 1 { "" } else { "s" },
-        env_name
-    );
+    // The whole file is re-encrypted, so every key and comment in it is kept.
+    let passphrase = if remove { None } else { Some(new_passphrase()?) };
+    identity_file.write(passphrase.as_ref())?;
 
-    Ok(())
+    let report = IdentityReport {
+        identity: bundle.path.display().to_string(),
+        public_key: bundle.recipient().to_string(),
+        passphrase_protected: passphrase.is_some(),
+    };
+    output.print(&report, || {
+        let action = match (bundle.passphrase_protected, remove) {
+            (_, true) => "Removed the passphrase from",
+            (true, false) => "Changed the passphrase of",
+            (false, false) => "Set a passphrase on",
+        };
+        println!("✓ {action} identity key at {}", bundle.path.display());
+    })
 }
 
-fn cmd_get(env_name: &str, key: &str, identity_override: Option<&Path>) -> Result<()> {
-    require_m1_env(env_name)?;
+fn cmd_git_install(cwd: &Path, output: OutputFormat) -> Result<()> {
+    let root = repo_root(cwd)?;
+    let report = GitInstallReport { attributes_added: git::install(&root)? };
+
+    output.print(&report, || {
+        println!("✓ Registered the envkey merge and diff drivers in the local git config");
+        match &report.attributes_added {
+            Some(line) => println!("✓ Wrote `{line}` to .gitattributes; commit it to share it"),
+            None => {
+                println!("✓ .gitattributes already routes {} to envkey", git::ATTRIBUTES_PATTERN);
+            }
+        }
+        println!(
+            "  Each clone needs `envkey git install` once, since git config is not committed."
+        );
+    })
+}
 
-    let cwd = env::current_dir()?;
-    let envkey_path = envkey_path(&cwd);
-    if !envkey_path.exists() {
-        return Err(EnvkeyError::message(
-            "missing .envkey in current directory; run `envkey init` first",
+/// Values are shown as digests of their plaintext keyed by the environment's data key, so
+/// `git diff` only shows real changes without exposing guessable values; without an
+/// identity that can read an environment its values show as `<encrypted>`.
+fn cmd_git_diff_textconv(
+    path: &Path,
+    show_values: bool,
+    identity_override: &[PathBuf],
+) -> Result<()> {
+    let file = read_unverified_envkey(path)?;
+    let identity = load_identities(identity_override).ok().map(|bundle| bundle.identity);
+
+    let mut out = String::new();
+    // Git shows this in diffs and logs, so a roster that fails verification is flagged
+    // rather than left out; commands that act on the file still refuse it.
+    if let Err(err) = verify_envkey_roster(&file) {
+        out.push_str(&format!("# WARNING: {err}\n"));
+    }
+    out.push_str(&format!("version {}\n", file.migrated_from.unwrap_or(file.version)));
+
+    out.push_str("\n[team]\n");
+    for (name, member) in &file.team {
+        let environments =
+            member.environments.as_ref().map_or("all".to_string(), |envs| envs.join(","));
+        out.push_str(&format!(
+            "{name} role={} environments={environments} pubkey={} signed_by={}\n",
+            role_label(&member.role),
+            member.pubkey,
+            member.signed_by
         ));
     }
 
-    let file = read_envkey(&envkey_path)?;
-    let identity = load_identity_from(&resolve_identity_path(identity_override)?)?;
-
-    let env = file
-        .default_env()
-        .ok_or_else(|| EnvkeyError::message("default environment not found in .envkey"))?;
-    let entry =
-        env.get(key).ok_or_else(|| EnvkeyError::message(format!("secret key not found: {key}")))?;
+    for (env_name, env) in &file.environments {
+        out.push_str(&format!("\n[environment {env_name}]\n"));
+        let decryptor = identity
+            .as_deref()
+            .and_then(|identity| EnvDecryptor::new(&file, env_name, identity).ok());
+        if let Some(data_key) = decryptor.as_ref().and_then(EnvDecryptor::data_key) {
+            out.push_str(&format!("data key {}\n", data_key.fingerprint()));
+        }
+        for (key, entry) in &env.secrets {
+            out.push_str(&format!(
+                "{key} = {} (set by {} at {})\n",
+                textconv_value(decryptor.as_ref(), key, entry, show_values),
+                entry.set_by,
+                entry.modified
+            ));
+        }
+    }
 
-    let plaintext = decrypt_value(&entry.value, &identity.identity)?;
-    println!("{plaintext}");
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(out.as_bytes())?;
+    stdout.flush()?;
     Ok(())
 }
 
-fn cmd_ls(env_name: &str) -> Result<()> {
-    require_m1_env(env_name)?;
-
-    let cwd = env::current_dir()?;
-    let envkey_path = envkey_path(&cwd);
-    if !envkey_path.exists() {
-        return Err(EnvkeyError::message(
-            "missing .envkey in current directory; run `envkey init` first",
-        ));
-    }
-
-    let file = read_envkey(&envkey_path)?;
-    let Some(env) = file.default_env() else {
-        println!("ENVIRONMENT  KEY  SET_BY  MODIFIED");
-        return Ok(());
+fn textconv_value(
+    decryptor: Option<&EnvDecryptor>,
+    key: &str,
+    entry: &SecretEntry,
+    show_values: bool,
+) -> String {
+    let Some(decryptor) = decryptor else {
+        return "<encrypted>".to_string();
     };
+    let Ok(plaintext) = decryptor.decrypt(key, &entry.value) else {
+        return "<cannot decrypt>".to_string();
+    };
+    if !show_values {
+        return match decryptor.data_key() {
+            Some(data_key) => {
+                format!("hmac:{}", data_key.value_digest(key, plaintext.expose_secret()))
+            }
+            // Values from before data keys have nothing to key a digest with; a digest of
+            // the ciphertext still shows when they were set again.
+            None => format!("ciphertext:{}", digest_hex(entry.value.as_bytes())),
+        };
+    }
+    display_plaintext(entry.encoding(), plaintext.expose_secret())
+}
 
-    let mut rows: Vec<(String, String, String, String)> = env
-        .iter()
-        .map(|(key, entry)| {
-            ("default".to_string(), key.clone(), entry.set_by.clone(), entry.modified.clone())
-        })
-        .collect();
-
-    rows.sort_by(|a, b| a.1.cmp(&b.1));
+/// Single-line rendering of a decrypted value: quoted and escaped text, or base64.
+fn display_plaintext(encoding: SecretEncoding, bytes: &[u8]) -> String {
+    match (encoding, std::str::from_utf8(bytes)) {
+        (SecretEncoding::Utf8, Ok(text)) => format!("\"{}\"", text.escape_debug()),
+        _ => format!("base64:{}", STANDARD.encode(bytes)),
+    }
+}
 
-    let env_w = rows
-        .iter()
-        .map(|row| row.0.len())
-        .max()
-        .unwrap_or("ENVIRONMENT".len())
-        .max("ENVIRONMENT".len());
-    let key_w = rows.iter().map(|row| row.1.len()).max().unwrap_or("KEY".len()).max("KEY".len());
-    let set_by_w =
-        rows.iter().map(|row| row.2.len()).max().unwrap_or("SET_BY".len()).max("SET_BY".len());
+/// Git passes temporary copies of the three versions and expects the result in `ours`;
+/// a non-zero exit leaves the file marked as conflicted.
+fn cmd_merge_driver(
+    base: &Path,
+    ours: &Path,
+    theirs: &Path,
+    identity_override: &[PathBuf],
+) -> Result<()> {
+    let base_file = read_envkey(base)?;
+    let ours_file = read_envkey(ours)?;
+    let theirs_file = read_envkey(theirs)?;
+    for (side, file) in [("our", &ours_file), ("their", &theirs_file)] {
+        if let Some(version) = file.migrated_from {
+            return Err(EnvkeyError::message(format!(
+                "cannot merge .envkey: {side} side uses format version {version}; run \
+                 `envkey migrate` on both branches first"
+            )));
+        }
+    }
 
-    println!("{:<env_w$}  {:<key_w$}  {:<set_by_w$}  MODIFIED", "ENVIRONMENT", "KEY", "SET_BY");
+    let MergeResult { mut file, mut conflicts, rekey, reseal } =
+        merge_files(&base_file, &ours_file, &theirs_file);
+    if reseal && let Err(err) = seal_merged_roster(&mut file, identity_override) {
+        conflicts.push(format!(
+            "team roster was changed on both sides and needs an admin to seal the merge: {err}"
+        ));
+        file.team = ours_file.team.clone();
+        file.roster = ours_file.roster.clone();
+    }
+    match verify_roster(&file) {
+        Ok(()) => conflicts.extend(rekey_merged_envs(&mut file, &rekey, identity_override)),
+        Err(err) => conflicts.push(format!("merged team roster does not verify: {err}")),
+    }
 
-    for (env_name, key, set_by, modified) in rows {
-        println!("{:<env_w$}  {:<key_w$}  {:<set_by_w$}  {}", env_name, key, set_by, modified);
+    write_envkey_atomic(ours, &file)?;
+    if conflicts.is_empty() {
+        return Ok(());
     }
+    Err(EnvkeyError::message(format!(
+        ".envkey could not be merged automatically; our side was kept for:\n  {}",
+        conflicts.join("\n  ")
+    )))
+}
 
-    Ok(())
+/// Seals a team that both sides of a merge changed, as the admin running the merge.
+fn seal_merged_roster(file: &mut EnvkeyFile, identity_override: &[PathBuf]) -> Result<()> {
+    verify_members(file)?;
+    let identity_bundle = load_identities(identity_override)?;
+    let (admin, signing_key) = require_admin_identity(file, &identity_bundle)?;
+    seal_roster(file, &admin, signing_key)
 }
 
 fn cmd_member_add(
+    envkey_path: &Path,
     name: &str,
     pubkey: Option<&str>,
     role: Role,
-    identity_override: Option<&Path>,
-) -> Result<()> {
-    let cwd = env::current_dir()?;
-    let envkey_path = envkey_path(&cwd);
-    let identity_bundle = load_identity_from(&resolve_identity_path(identity_override)?)?;
+    envs: Vec<String>,
+    identity_override: &[PathBuf],
+) -> Result<MemberReport> {
+    for env_name in &envs {
+        validate_environment_name(env_name)?;
+    }
+    let environments = if envs.is_empty() { None } else { Some(envs) };
+
+    let identity_bundle = load_identities(identity_override)?;
     let (recipient, ci_private_key) = resolve_member_add_recipient(name, pubkey, &role)?;
-    let mut reencrypted = 0usize;
+    check_admin_pubkey(name, &role, &recipient)?;
+    let mut rewrapped = Vec::new();
 
-    let role_text = role_label(&role);
-    with_envkey_lock(&envkey_path, || {
+    with_envkey_lock(envkey_path, || {
         if !envkey_path.exists() {
-            return Err(EnvkeyError::message(
-                "missing .envkey in current directory; run `envkey init` first",
-            ));
+            return Err(missing_envkey(envkey_path));
         }
 
-        let mut file = read_envkey(&envkey_path)?;
-        require_admin_identity(&file, &identity_bundle.identity)?;
+        let mut file = read_trusted_envkey(envkey_path)?;
+        let (current_admin_name, signing_key) = require_admin_identity(&file, &identity_bundle)?;
 
         if file.team.contains_key(name) {
             return Err(EnvkeyError::message(format!("team member already exists: {name}")));
@@ -345,50 +2210,52 @@ fn cmd_member_add(
         file.team.insert(
             name.to_string(),
             TeamMember {
-                pubkey: recipient.to_string(),
+                pubkey: recipient,
                 role: role.clone(),
                 added: now_date(),
-                environments: None,
+                environments: environments.clone(),
+                signed_by: String::new(),
+                signature: String::new(),
             },
         );
+        sign_member(&mut file, name, &current_admin_name, signing_key)?;
+        seal_roster(&mut file, &current_admin_name, signing_key)?;
 
-        reencrypted = reencrypt_all_secrets(&mut file, &identity_bundle.identity)?;
-        write_envkey_atomic(&envkey_path, &file)?;
+        let env_names = accessible_environments(&file, name);
+        rewrapped = rewrap_data_keys(&mut file, &env_names, identity_bundle.identity.as_ref())?;
+        write_envkey_atomic(envkey_path, &file)?;
         Ok(())
     })?;
 
-    println!(
-        "✓ Added {} ({}) — re-encrypted {} secret{} in default",
-        name,
-        role_text,
-        reencrypted,
-        if reencrypted == 1 { "" } else { "s" }
-    );
-    if let Some(private_key) = ci_private_key {
-        println!("✓ Generated CI key pair");
-        println!("✓ Private key (add this to your CI secrets as ENVKEY_IDENTITY):");
-        println!("{private_key}");
-    }
-    Ok(())
+    Ok(MemberReport {
+        action: "add",
+        name: name.to_string(),
+        role: Some(role),
+        environments: environments.unwrap_or_default(),
+        rewrapped,
+        rotated: Vec::new(),
+        ci_private_key,
+    })
 }
 
-fn cmd_member_update(name: &str, pubkey: &str, identity_override: Option<&Path>) -> Result<()> {
-    let cwd = env::current_dir()?;
-    let envkey_path = envkey_path(&cwd);
-    let identity_bundle = load_identity_from(&resolve_identity_path(identity_override)?)?;
-    let recipient = x25519::Recipient::from_str(pubkey)
+fn cmd_member_update(
+    envkey_path: &Path,
+    name: &str,
+    pubkey: &str,
+    identity_override: &[PathBuf],
+) -> Result<MemberReport> {
+    let identity_bundle = load_identities(identity_override)?;
+    let recipient = canonical_pubkey(pubkey)
         .map_err(|err| EnvkeyError::message(format!("invalid age public key for {name}: {err}")))?;
-    let mut reencrypted = 0usize;
+    let mut rotated = Vec::new();
 
-    with_envkey_lock(&envkey_path, || {
+    with_envkey_lock(envkey_path, || {
         if !envkey_path.exists() {
-            return Err(EnvkeyError::message(
-                "missing .envkey in current directory; run `envkey init` first",
-            ));
+            return Err(missing_envkey(envkey_path));
         }
 
-        let mut file = read_envkey(&envkey_path)?;
-        let current_admin_name = require_admin_identity(&file, &identity_bundle.identity)?;
+        let mut file = read_trusted_envkey(envkey_path)?;
+        let (current_admin_name, signing_key) = require_admin_identity(&file, &identity_bundle)?;
 
         if name == current_admin_name {
             return Err(EnvkeyError::message(
@@ -400,42 +2267,48 @@ fn cmd_member_update(name: &str, pubkey: &str, identity_override: Option<&Path>)
             .team
             .get_mut(name)
             .ok_or_else(|| EnvkeyError::message(format!("team member not found: {name}")))?;
-        if member.pubkey == recipient.to_string() {
+        if member.pubkey == recipient {
             return Err(EnvkeyError::message(format!(
                 "new public key matches existing key for {name}"
             )));
         }
-        member.pubkey = recipient.to_string();
+        check_admin_pubkey(name, &member.role, &recipient)?;
+        member.pubkey = recipient;
+        reseal_roster(&mut file, &current_admin_name, signing_key)?;
 
-        reencrypted = reencrypt_all_secrets(&mut file, &identity_bundle.identity)?;
-        write_envkey_atomic(&envkey_path, &file)?;
+        let env_names = accessible_environments(&file, name);
+        rotated = rotate_data_keys(&mut file, &env_names, identity_bundle.identity.as_ref())?;
+        write_envkey_atomic(envkey_path, &file)?;
         Ok(())
     })?;
 
-    println!(
-        "✓ Updated {} public key — re-encrypted {} secret{} in default",
-        name,
-        reencrypted,
-        if reencrypted == 1 { "" } else { "s" }
-    );
-    Ok(())
+    Ok(MemberReport {
+        action: "update",
+        name: name.to_string(),
+        role: None,
+        environments: Vec::new(),
+        rewrapped: Vec::new(),
+        rotated,
+        ci_private_key: None,
+    })
 }
 
-fn cmd_member_rm(name: &str, yes: bool, identity_override: Option<&Path>) -> Result<()> {
-    let cwd = env::current_dir()?;
-    let envkey_path = envkey_path(&cwd);
-    let identity_bundle = load_identity_from(&resolve_identity_path(identity_override)?)?;
-    let mut reencrypted = 0usize;
+fn cmd_member_rm(
+    envkey_path: &Path,
+    name: &str,
+    yes: bool,
+    identity_override: &[PathBuf],
+) -> Result<MemberReport> {
+    let identity_bundle = load_identities(identity_override)?;
+    let mut rotated = Vec::new();
 
-    with_envkey_lock(&envkey_path, || {
+    with_envkey_lock(envkey_path, || {
         if !envkey_path.exists() {
-            return Err(EnvkeyError::message(
-                "missing .envkey in current directory; run `envkey init` first",
-            ));
+            return Err(missing_envkey(envkey_path));
         }
 
-        let mut file = read_envkey(&envkey_path)?;
-        let current_admin_name = require_admin_identity(&file, &identity_bundle.identity)?;
+        let mut file = read_trusted_envkey(envkey_path)?;
+        let (current_admin_name, signing_key) = require_admin_identity(&file, &identity_bundle)?;
 
         if !file.team.contains_key(name) {
             return Err(EnvkeyError::message(format!("team member not found: {name}")));
@@ -448,38 +2321,155 @@ fn cmd_member_rm(name: &str, yes: bool, identity_override: Option<&Path>) -> Res
             return Err(EnvkeyError::message("aborted"));
         }
 
+        let env_names = accessible_environments(&file, name);
         file.team.remove(name);
+        reseal_roster(&mut file, &current_admin_name, signing_key)?;
 
-        reencrypted = reencrypt_all_secrets(&mut file, &identity_bundle.identity)?;
-        write_envkey_atomic(&envkey_path, &file)?;
+        rotated = rotate_data_keys(&mut file, &env_names, identity_bundle.identity.as_ref())?;
+        write_envkey_atomic(envkey_path, &file)?;
         Ok(())
     })?;
 
-    println!(
-        "✓ Removed {} — re-encrypted {} secret{} in default",
-        name,
-        reencrypted,
-        if reencrypted == 1 { "" } else { "s" }
-    );
-    Ok(())
+    Ok(MemberReport {
+        action: "rm",
+        name: name.to_string(),
+        role: None,
+        environments: Vec::new(),
+        rewrapped: Vec::new(),
+        rotated,
+        ci_private_key: None,
+    })
 }
 
-fn cmd_member_role_set(name: &str, role: Role, identity_override: Option<&Path>) -> Result<()> {
-    let cwd = env::current_dir()?;
-    let envkey_path = envkey_path(&cwd);
-    let identity_bundle = load_identity_from(&resolve_identity_path(identity_override)?)?;
-    let mut reencrypted = 0usize;
-    let new_role_text = role_label(&role);
+fn cmd_member_grant(
+    envkey_path: &Path,
+    name: &str,
+    envs: &[String],
+    identity_override: &[PathBuf],
+) -> Result<MemberReport> {
+    for env_name in envs {
+        validate_environment_name(env_name)?;
+    }
+
+    let identity_bundle = load_identities(identity_override)?;
+    let mut rewrapped = Vec::new();
 
-    with_envkey_lock(&envkey_path, || {
+    with_envkey_lock(envkey_path, || {
         if !envkey_path.exists() {
-            return Err(EnvkeyError::message(
-                "missing .envkey in current directory; run `envkey init` first",
-            ));
+            return Err(missing_envkey(envkey_path));
+        }
+
+        let mut file = read_trusted_envkey(envkey_path)?;
+        let (current_admin_name, signing_key) = require_admin_identity(&file, &identity_bundle)?;
+
+        let member = file
+            .team
+            .get_mut(name)
+            .ok_or_else(|| EnvkeyError::message(format!("team member not found: {name}")))?;
+        let Some(granted) = member.environments.as_mut() else {
+            return Err(EnvkeyError::message(format!(
+                "member {name} already has access to all environments"
+            )));
+        };
+        for env_name in envs {
+            if granted.contains(env_name) {
+                return Err(EnvkeyError::message(format!(
+                    "member {name} already has access to {env_name}"
+                )));
+            }
+            granted.push(env_name.clone());
+        }
+        granted.sort();
+        granted.dedup();
+        reseal_roster(&mut file, &current_admin_name, signing_key)?;
+
+        rewrapped = rewrap_data_keys(&mut file, envs, identity_bundle.identity.as_ref())?;
+        write_envkey_atomic(envkey_path, &file)?;
+        Ok(())
+    })?;
+
+    Ok(MemberReport {
+        action: "grant",
+        name: name.to_string(),
+        role: None,
+        environments: envs.to_vec(),
+        rewrapped,
+        rotated: Vec::new(),
+        ci_private_key: None,
+    })
+}
+
+fn cmd_member_revoke(
+    envkey_path: &Path,
+    name: &str,
+    envs: &[String],
+    identity_override: &[PathBuf],
+) -> Result<MemberReport> {
+    for env_name in envs {
+        validate_environment_name(env_name)?;
+    }
+
+    let identity_bundle = load_identities(identity_override)?;
+    let mut rotated = Vec::new();
+
+    with_envkey_lock(envkey_path, || {
+        if !envkey_path.exists() {
+            return Err(missing_envkey(envkey_path));
+        }
+
+        let mut file = read_trusted_envkey(envkey_path)?;
+        let (current_admin_name, signing_key) = require_admin_identity(&file, &identity_bundle)?;
+        if name == current_admin_name {
+            return Err(EnvkeyError::message("cannot revoke your own environment access"));
+        }
+
+        let all_envs: Vec<String> = file.environments.keys().cloned().collect();
+        let member = file
+            .team
+            .get_mut(name)
+            .ok_or_else(|| EnvkeyError::message(format!("team member not found: {name}")))?;
+        let granted = member.environments.get_or_insert(all_envs);
+        for env_name in envs {
+            if !granted.contains(env_name) {
+                return Err(EnvkeyError::message(format!(
+                    "member {name} does not have access to {env_name}"
+                )));
+            }
+            granted.retain(|granted_env| granted_env != env_name);
+        }
+        reseal_roster(&mut file, &current_admin_name, signing_key)?;
+
+        rotated = rotate_data_keys(&mut file, envs, identity_bundle.identity.as_ref())?;
+        write_envkey_atomic(envkey_path, &file)?;
+        Ok(())
+    })?;
+
+    Ok(MemberReport {
+        action: "revoke",
+        name: name.to_string(),
+        role: None,
+        environments: envs.to_vec(),
+        rewrapped: Vec::new(),
+        rotated,
+        ci_private_key: None,
+    })
+}
+
+fn cmd_member_role_set(
+    envkey_path: &Path,
+    name: &str,
+    role: Role,
+    identity_override: &[PathBuf],
+) -> Result<MemberReport> {
+    let identity_bundle = load_identities(identity_override)?;
+
+    with_envkey_lock(envkey_path, || {
+        if !envkey_path.exists() {
+            return Err(missing_envkey(envkey_path));
         }
 
-        let mut file = read_envkey(&envkey_path)?;
-        let current_admin_name = require_admin_identity(&file, &identity_bundle.identity)?;
+        let mut file = read_trusted_envkey(envkey_path)?;
+        let (current_admin_name, signing_key) = require_admin_identity(&file, &identity_bundle)?;
 
         if name == current_admin_name && role != Role::Admin {
             return Err(EnvkeyError::message("cannot change your own admin role in M2"));
@@ -495,33 +2485,46 @@ fn cmd_member_role_set(name: &str, role: Role, identity_override: Option<&Path>)
                 "member {name} already has role {current_role_text}"
             )));
         }
+        check_admin_pubkey(name, &role, &member.pubkey)?;
         member.role = role.clone();
-
-        reencrypted = reencrypt_all_secrets(&mut file, &identity_bundle.identity)?;
-        write_envkey_atomic(&envkey_path, &file)?;
+        reseal_roster(&mut file, &current_admin_name, signing_key)?;
+        write_envkey_atomic(envkey_path, &file)?;
         Ok(())
     })?;
 
-    println!(
-        "✓ Updated {} role to {} — re-encrypted {} secret{} in default",
-        name,
-        new_role_text,
-        reencrypted,
-        if reencrypted == 1 { "" } else { "s" }
-    );
-    Ok(())
+    Ok(MemberReport {
+        action: "role-set",
+        name: name.to_string(),
+        role: Some(role),
+        environments: Vec::new(),
+        rewrapped: Vec::new(),
+        rotated: Vec::new(),
+        ci_private_key: None,
+    })
 }
 
-fn cmd_member_ls() -> Result<()> {
-    let cwd = env::current_dir()?;
-    let envkey_path = envkey_path(&cwd);
+fn cmd_member_ls(envkey_path: &Path, output: OutputFormat) -> Result<()> {
     if !envkey_path.exists() {
-        return Err(EnvkeyError::message(
-            "missing .envkey in current directory; run `envkey init` first",
-        ));
+        return Err(missing_envkey(envkey_path));
+    }
+
+    let file = read_trusted_envkey(envkey_path)?;
+    if !output.is_table() {
+        let members = file
+            .team
+            .iter()
+            .map(|(name, member)| MemberInfo {
+                name: name.clone(),
+                pubkey: member.pubkey.clone(),
+                role: member.role.clone(),
+                environments: member.environments.clone(),
+                added: member.added.clone(),
+                signed_by: member.signed_by.clone(),
+            })
+            .collect();
+        return output.print(&MembersReport { members }, || {});
     }
 
-    let file = read_envkey(&envkey_path)?;
     let mut rows: Vec<(String, String, String, String)> = file
         .team
         .iter()
@@ -529,7 +2532,7 @@ fn cmd_member_ls() -> Result<()> {
             (
                 name.clone(),
                 role_label(&member.role).to_string(),
-                "default".to_string(),
+                environments_label(member),
                 member.added.clone(),
             )
         })
@@ -553,58 +2556,272 @@ fn cmd_member_ls() -> Result<()> {
     Ok(())
 }
 
-fn parse_recipients_from_team(file: &EnvkeyFile) -> Result<Vec<x25519::Recipient>> {
+fn parse_recipients_for_env(
+    file: &EnvkeyFile,
+    env_name: &str,
+) -> Result<Vec<Box<dyn age::Recipient>>> {
     file.team
         .values()
+        .filter(|member| member.can_access(env_name))
         .map(|member| {
-            x25519::Recipient::from_str(&member.pubkey).map_err(|err| {
+            parse_recipient(&member.pubkey).map_err(|err| {
                 EnvkeyError::message(format!("invalid team public key {}: {err}", member.pubkey))
             })
         })
         .collect()
 }
 
-fn resolve_member_for_identity(
+/// Finds an admin among the loaded identities, returning their name and the key to sign
+/// the team roster with.
+fn require_admin_identity<'a>(
     file: &EnvkeyFile,
-    identity: &x25519::Identity,
-) -> Result<(String, Role)> {
-    let current_pubkey = identity.to_public().to_string();
-    file.team
+    identity: &'a IdentityBundle,
+) -> Result<(String, &'a x25519::Identity)> {
+    let admin = |pubkey: &str| {
+        file.team.iter().find(|(_, member)| member.role == Role::Admin && member.pubkey == pubkey)
+    };
+    let (name, member) = identity
+        .find_recipient(|pubkey| admin(pubkey).is_some())?
+        .and_then(admin)
+        .ok_or_else(|| EnvkeyError::message("current identity is not an admin in .envkey"))?;
+    Ok((name.clone(), identity.signing_key_for(&member.pubkey)?))
+}
+
+/// Returns the data key for writing to `env_name`, creating one wrapped to the
+/// environment's recipients if it has none yet.
+fn data_key_for_write(
+    file: &mut EnvkeyFile,
+    env_name: &str,
+    identity: &dyn age::Identity,
+) -> Result<DataKey> {
+    if let Some(wrapped) = file.environments.get(env_name).and_then(|env| env.data_key.as_deref()) {
+        return DataKey::unwrap(env_name, wrapped, identity);
+    }
+
+    let recipients = recipients_for_write(file, env_name)?;
+    let data_key = DataKey::generate(env_name);
+    let wrapped = data_key.wrap(&recipients)?;
+    // Refuse to encrypt into an environment the writer could not read back.
+    DataKey::unwrap(env_name, &wrapped, identity)?;
+    file.environments.entry(env_name.to_string()).or_default().data_key = Some(wrapped);
+    Ok(data_key)
+}
+
+/// Brings environments whose data key changed on both sides of a merge under one key,
+/// wrapped to the merged roster. A key rotated on one side wins, since the rotation shut
+/// out someone who kept the old key; if both sides rotated, the environment gets a fresh
+/// key. Returns a conflict description for each environment that could not be fixed.
+fn rekey_merged_envs(
+    file: &mut EnvkeyFile,
+    rekey: &[RekeyEnv],
+    identity_override: &[PathBuf],
+) -> Vec<String> {
+    if rekey.is_empty() {
+        return Vec::new();
+    }
+    let identity_bundle = match load_identities(identity_override) {
+        Ok(bundle) => bundle,
+        Err(err) => {
+            return rekey
+                .iter()
+                .map(|env| {
+                    format!(
+                        "environment {}: data key changed on both sides and no identity can \
+                         re-encrypt it ({err})",
+                        env.env_name
+                    )
+                })
+                .collect();
+        }
+    };
+
+    rekey
         .iter()
-        .find(|(_, member)| member.pubkey == current_pubkey)
-        .map(|(name, member)| (name.clone(), member.role.clone()))
-        .ok_or_else(|| EnvkeyError::message("current identity is not an admin in .envkey"))
+        .filter_map(|env| {
+            rekey_merged_env(file, env, identity_bundle.identity.as_ref())
+                .err()
+                .map(|err| format!("environment {}: {err}", env.env_name))
+        })
+        .collect()
+}
+
+fn rekey_merged_env(
+    file: &mut EnvkeyFile,
+    rekey: &RekeyEnv,
+    identity: &dyn age::Identity,
+) -> Result<()> {
+    let env_name = rekey.env_name.as_str();
+    let unwrap = |wrapped: &Option<String>| {
+        wrapped.as_deref().map(|wrapped| DataKey::unwrap(env_name, wrapped, identity)).transpose()
+    };
+    let (ours, theirs) = match (unwrap(&rekey.ours)?, unwrap(&rekey.theirs)?) {
+        (Some(ours), Some(theirs)) => (ours, theirs),
+        (Some(data_key), None) | (None, Some(data_key)) => {
+            return wrap_merged_data_key(file, env_name, &data_key);
+        }
+        (None, None) => return Ok(()),
+    };
+
+    // Rewrapping keeps the key itself, so only a key unlike the base one was rotated.
+    let base = unwrap(&rekey.base)?.map(|base| base.fingerprint());
+    let rotated = |data_key: &DataKey| base.as_deref() != Some(&data_key.fingerprint());
+    let fresh;
+    let target = match (rotated(&ours), rotated(&theirs)) {
+        (true, true) if ours.fingerprint() != theirs.fingerprint() => {
+            fresh = DataKey::generate(env_name);
+            &fresh
+        }
+        (false, true) => &theirs,
+        _ => &ours,
+    };
+
+    let env = file.env_mut(env_name);
+    for (key, entry) in env.iter_mut() {
+        let source = if rekey.from_theirs.contains(key) { &theirs } else { &ours };
+        if source.fingerprint() != target.fingerprint() {
+            let plaintext: SecretSlice<u8> = source.open(key, &entry.value)?.into();
+            entry.value = target.seal(key, plaintext.expose_secret())?;
+        }
+    }
+    wrap_merged_data_key(file, env_name, target)
+}
+
+fn wrap_merged_data_key(file: &mut EnvkeyFile, env_name: &str, data_key: &DataKey) -> Result<()> {
+    let recipients = recipients_for_write(file, env_name)?;
+    let env = file.environments.get_mut(env_name).expect("environment exists");
+    env.data_key = Some(data_key.wrap(&recipients)?);
+    Ok(())
+}
+
+/// Re-encrypts the data key of each environment to its current recipients, leaving
+/// secret values untouched. Returns the environments that had a data key to rewrap.
+fn rewrap_data_keys(
+    file: &mut EnvkeyFile,
+    env_names: &[String],
+    identity: &dyn age::Identity,
+) -> Result<Vec<String>> {
+    let mut rewrapped = Vec::new();
+    for env_name in env_names {
+        let Some(wrapped) = file.environments.get(env_name).and_then(|env| env.data_key.clone())
+        else {
+            continue;
+        };
+        let recipients = recipients_for_write(file, env_name)?;
+        let data_key = DataKey::unwrap(env_name, &wrapped, identity)?;
+        let env = file.environments.get_mut(env_name).expect("environment exists");
+        env.data_key = Some(data_key.wrap(&recipients)?);
+        rewrapped.push(env_name.clone());
+    }
+    Ok(rewrapped)
+}
+
+/// Rotates the data key of each of `env_names` that has one, so a key kept by someone who
+/// lost access opens nothing written from now on. Returns the rotated environments.
+fn rotate_data_keys(
+    file: &mut EnvkeyFile,
+    env_names: &[String],
+    identity: &dyn age::Identity,
+) -> Result<Vec<String>> {
+    let mut rotated = Vec::new();
+    for env_name in env_names {
+        if file.environments.get(env_name).is_some_and(|env| env.data_key.is_some()) {
+            rotate_data_key(file, env_name, identity)?;
+            rotated.push(env_name.clone());
+        }
+    }
+    Ok(rotated)
 }
 
-fn require_admin_identity(file: &EnvkeyFile, identity: &x25519::Identity) -> Result<String> {
-    let (name, role) = resolve_member_for_identity(file, identity)?;
-    if role != Role::Admin {
-        return Err(EnvkeyError::message("current identity is not an admin in .envkey"));
+/// Replaces an environment's data key and re-encrypts every secret under the new key.
+/// Returns the number of secrets re-encrypted.
+fn rotate_data_key(
+    file: &mut EnvkeyFile,
+    env_name: &str,
+    identity: &dyn age::Identity,
+) -> Result<usize> {
+    let decryptor = EnvDecryptor::new(file, env_name, identity)?;
+    let recipients = recipients_for_write(file, env_name)?;
+    let data_key = DataKey::generate(env_name);
+    let wrapped = data_key.wrap(&recipients)?;
+
+    let env = file
+        .environments
+        .get_mut(env_name)
+        .ok_or_else(|| EnvkeyError::message(format!("environment not found: {env_name}")))?;
+    for (key, entry) in env.secrets.iter_mut() {
+        let plaintext = decryptor.decrypt(key, &entry.value)?;
+        entry.value = data_key.seal(key, plaintext.expose_secret())?;
     }
-    Ok(name)
+    env.data_key = Some(wrapped);
+    Ok(env.secrets.len())
 }
 
-fn reencrypt_all_secrets(file: &mut EnvkeyFile, identity: &x25519::Identity) -> Result<usize> {
-    let recipients = parse_recipients_from_team(file)?;
+fn recipients_for_write(file: &EnvkeyFile, env_name: &str) -> Result<Vec<Box<dyn age::Recipient>>> {
+    ensure_current_roster(file)?;
+    let recipients = parse_recipients_for_env(file, env_name)?;
     if recipients.is_empty() {
-        return Err(EnvkeyError::message("no team recipients found in .envkey; cannot encrypt"));
+        return Err(EnvkeyError::message(format!(
+            "no team recipients have access to environment {env_name}; cannot encrypt"
+        )));
     }
+    Ok(recipients)
+}
 
-    let mut count = 0usize;
-    for env in file.environments.values_mut() {
-        for entry in env.values_mut() {
-            let plaintext = decrypt_value(&entry.value, identity)?;
-            entry.value = encrypt_value(&plaintext, &recipients)?;
-            count += 1;
+fn accessible_environments(file: &EnvkeyFile, name: &str) -> Vec<String> {
+    let Some(member) = file.team.get(name) else {
+        return Vec::new();
+    };
+    file.environments.keys().filter(|env_name| member.can_access(env_name)).cloned().collect()
+}
+
+fn describe_rewrapped(env_names: &[String]) -> String {
+    match env_names.len() {
+        0 => "no data keys to rewrap".to_string(),
+        1 => format!("rewrapped data key for {}", env_names[0]),
+        _ => format!("rewrapped data keys for {}", env_names.join(", ")),
+    }
+}
+
+fn describe_rotated(env_names: &[String]) -> String {
+    match env_names.len() {
+        0 => "no data keys to rotate".to_string(),
+        1 => format!("rotated data key for {}", env_names[0]),
+        _ => format!("rotated data keys for {}", env_names.join(", ")),
+    }
+}
+
+fn print_member_report(report: &MemberReport) {
+    let name = &report.name;
+    let rewrapped = describe_rewrapped(&report.rewrapped);
+    let rotated = describe_rotated(&report.rotated);
+    match report.action {
+        "add" => {
+            let role = report.role.as_ref().map_or("member", role_label);
+            println!("✓ Added {name} ({role}) — {rewrapped}");
+        }
+        "update" => println!("✓ Updated {name} public key — {rotated}"),
+        "rm" => println!("✓ Removed {name} — {rotated}"),
+        "grant" => {
+            println!("✓ Granted {name} access to {} — {rewrapped}", report.environments.join(", "));
+        }
+        "revoke" => {
+            println!("✓ Revoked {name} access to {} — {rotated}", report.environments.join(", "));
+        }
+        _ => {
+            let role = report.role.as_ref().map_or("member", role_label);
+            println!("✓ Updated {name} role to {role}");
         }
     }
-    Ok(count)
+    if let Some(private_key) = &report.ci_private_key {
+        println!("✓ Generated CI key pair");
+        println!("✓ Private key (add this to your CI secrets as ENVKEY_IDENTITY):");
+        println!("{private_key}");
+    }
 }
 
-fn confirm_member_removal(name: &str) -> Result<bool> {
-    println!("⚠ Removing {name} requires re-encrypting all accessible secrets.");
-    println!("  This generates new encryption keys that {name} cannot decrypt.");
-    print!("  Continue? [y/N] ");
+/// Asks `prompt` as a y/N question on the terminal; anything but `y` or `yes` declines.
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("  {prompt} [y/N] ");
     io::stdout().flush()?;
 
     let mut input = String::new();
@@ -613,6 +2830,51 @@ fn confirm_member_removal(name: &str) -> Result<bool> {
     Ok(answer == "y" || answer == "yes")
 }
 
+fn confirm_roster_trust(known: &KnownRoster, root: &str) -> Result<bool> {
+    println!("⚠ Trusting this roster lets these admins change the team from now on:");
+    for (name, pubkey) in &known.admins {
+        let label = if name == root { " (root)" } else { "" };
+        println!("  {name}{label} {pubkey}");
+    }
+    println!("  Check every admin and public key with your team before trusting them.");
+    confirm("Trust the roster?")
+}
+
+fn confirm_roster_signing(file: &EnvkeyFile, admin: &str) -> Result<bool> {
+    println!("⚠ Nothing vouches for this team roster yet; signing it makes {admin} vouch for:");
+    for (name, member) in &file.team {
+        println!(
+            "  {name} ({}, environments: {}) {}",
+            role_label(&member.role),
+            environments_label(member),
+            member.pubkey
+        );
+    }
+    println!("  Check every member and public key with your team before signing.");
+    confirm("Sign the roster?")
+}
+
+fn confirm_reopen_editor(err: &EnvkeyError) -> Result<bool> {
+    println!("⚠ {err}");
+    confirm("Reopen the editor to fix it?")
+}
+
+fn confirm_member_removal(name: &str) -> Result<bool> {
+    println!(
+        "⚠ Removing {name} replaces the data key of every environment they can read and \
+         re-encrypts its secrets."
+    );
+    confirm("Continue?")
+}
+
+fn environments_label(member: &TeamMember) -> String {
+    match &member.environments {
+        None => "all".to_string(),
+        Some(envs) if envs.is_empty() => "none".to_string(),
+        Some(envs) => envs.join(","),
+    }
+}
+
 fn role_label(role: &Role) -> &'static str {
     match role {
         Role::Admin => "admin",
@@ -626,9 +2888,9 @@ fn resolve_member_add_recipient(
     name: &str,
     pubkey: Option<&str>,
     role: &Role,
-) -> Result<(x25519::Recipient, Option<String>)> {
+) -> Result<(String, Option<String>)> {
     if let Some(pubkey) = pubkey {
-        let recipient = x25519::Recipient::from_str(pubkey).map_err(|err| {
+        let recipient = canonical_pubkey(pubkey).map_err(|err| {
             EnvkeyError::message(format!("invalid age public key for {name}: {err}"))
         })?;
         return Ok((recipient, None));
@@ -640,18 +2902,42 @@ fn resolve_member_add_recipient(
 
     let identity = x25519::Identity::generate();
     let private_key = identity.to_string().expose_secret().to_string();
-    Ok((identity.to_public(), Some(private_key)))
+    Ok((identity.to_public().to_string(), Some(private_key)))
 }
 
-fn resolve_init_identity_path(identity_override: Option<&Path>) -> Result<PathBuf> {
-    if let Some(path) = identity_override {
-        let path = expand_home_prefix(path)?;
-        validate_identity_file_path(&path)?;
-        return Ok(path);
+/// Admins sign the team roster with their age X25519 key, which SSH and plugin keys
+/// cannot do.
+fn check_admin_pubkey(name: &str, role: &Role, pubkey: &str) -> Result<()> {
+    if *role == Role::Admin && !is_x25519_pubkey(pubkey) {
+        return Err(EnvkeyError::message(format!(
+            "admin {name} needs an age public key to sign the team roster; \
+             SSH and plugin keys can only be used by other roles"
+        )));
     }
+    Ok(())
+}
 
-    if let Ok(path) = env::var("ENVKEY_IDENTITY") {
-        let path = expand_home_prefix(Path::new(&path))?;
+fn resolve_init_identity_path(identity_override: &[PathBuf]) -> Result<PathBuf> {
+    if identity_override.is_empty() {
+        if let Some((name, _)) = env_identity_key() {
+            return Err(EnvkeyError::message(format!(
+                "{name} holds an identity key, but init needs an identity file; pass one with \
+                 --identity"
+            )));
+        }
+    }
+    let overrides = if identity_override.is_empty() {
+        env_identity_paths()
+    } else {
+        identity_override.to_vec()
+    };
+    if overrides.len() > 1 {
+        return Err(EnvkeyError::message(
+            "init creates a single identity; pass one --identity path",
+        ));
+    }
+    if let Some(path) = overrides.first() {
+        let path = expand_home_prefix(path)?;
         validate_identity_file_path(&path)?;
         return Ok(path);
     }
@@ -719,12 +3005,22 @@ fn validate_secret_key(key: &str) -> Result<()> {
     Ok(())
 }
 
-fn require_m1_env(env_name: &str) -> Result<()> {
-    if env_name != "default" {
+fn validate_environment_name(env_name: &str) -> Result<()> {
+    let mut chars = env_name.chars();
+    let first =
+        chars.next().ok_or_else(|| EnvkeyError::message("environment name cannot be empty"))?;
+    if !first.is_ascii_lowercase() {
+        return Err(EnvkeyError::message(format!(
+            "invalid environment name `{env_name}`: must start with a-z"
+        )));
+    }
+
+    if !chars.all(|c| c == '_' || c == '-' || c.is_ascii_lowercase() || c.is_ascii_digit()) {
         return Err(EnvkeyError::message(format!(
-            "M1 supports only default environment; got `{env_name}`"
+            "invalid environment name `{env_name}`: use only a-z, 0-9, -, _"
         )));
     }
+
     Ok(())
 }
 
@@ -751,8 +3047,13 @@ mod tests {
     }
 
     #[test]
-    fn non_default_env_is_rejected() {
-        let err = require_m1_env("production").expect_err("must fail");
-        assert!(err.to_string().contains("M1 supports only default environment"));
+    fn validates_environment_name_rules() {
+        assert!(validate_environment_name("default").is_ok());
+        assert!(validate_environment_name("prod-eu_1").is_ok());
+
+        assert!(validate_environment_name("").is_err());
+        assert!(validate_environment_name("Production").is_err());
+        assert!(validate_environment_name("1staging").is_err());
+        assert!(validate_environment_name("stag ing").is_err());
     }
 }
@@ -1,17 +1,146 @@
-use std::io::Write;
+use std::io::{self, IsTerminal, Read, Write};
+use std::iter;
+use std::str::FromStr;
 
-use age::{Encryptor, Recipient, decrypt, x25519};
+use age::plugin::RecipientPluginV1;
+use age::secrecy::{ExposeSecret, SecretString};
+use age::ssh::ParseRecipientKeyError;
+use age::{Callbacks, Decryptor, Encryptor, Identity, Recipient, plugin, ssh, x25519};
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
+use bech32::FromBase32;
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::{Scalar, clamp_integer};
+use rand::{Rng, rng};
+use sha2::{Digest, Sha512};
+use zeroize::Zeroizing;
 
 use crate::error::{EnvkeyError, Result};
 
-pub fn encrypt_value(plaintext: &str, recipients: &[x25519::Recipient]) -> Result<String> {
+/// Domain separator for the XEdDSA nonce hash (`hash_1` in the XEdDSA spec).
+const XEDDSA_NONCE_PREFIX: [u8; 32] = {
+    let mut prefix = [0xff; 32];
+    prefix[0] = 0xfe;
+    prefix
+};
+
+/// Parses a team member's public key: an age X25519 key (`age1...`), an age plugin
+/// recipient (`age1<plugin>1...`, encrypted to by running `age-plugin-<plugin>`) or an SSH
+/// public key (`ssh-ed25519 ...`, `ssh-rsa ...`).
+pub fn parse_recipient(pubkey: &str) -> Result<Box<dyn Recipient>> {
+    if pubkey.starts_with("ssh-") {
+        let recipient = ssh::Recipient::from_str(pubkey).map_err(ssh_recipient_error)?;
+        return Ok(Box::new(recipient));
+    }
+
+    match x25519::Recipient::from_str(pubkey) {
+        Ok(recipient) => Ok(Box::new(recipient)),
+        Err(err) => {
+            let recipient =
+                plugin::Recipient::from_str(pubkey).map_err(|_| EnvkeyError::message(err))?;
+            let plugin = RecipientPluginV1::new(
+                recipient.plugin(),
+                std::slice::from_ref(&recipient),
+                &[],
+                PluginCallbacks,
+            )
+            .map_err(|err| EnvkeyError::message(err.to_string()))?;
+            Ok(Box::new(plugin))
+        }
+    }
+}
+
+/// The form a public key is stored in the team roster: SSH keys lose their comment, so
+/// the roster entry matches the key derived from the private key file.
+pub fn canonical_pubkey(pubkey: &str) -> Result<String> {
+    let pubkey = pubkey.trim();
+    if pubkey.starts_with("ssh-") {
+        return Ok(ssh::Recipient::from_str(pubkey).map_err(ssh_recipient_error)?.to_string());
+    }
+    match x25519::Recipient::from_str(pubkey) {
+        Ok(recipient) => Ok(recipient.to_string()),
+        Err(err) => Ok(plugin::Recipient::from_str(pubkey)
+            .map_err(|_| EnvkeyError::message(err))?
+            .to_string()),
+    }
+}
+
+/// Whether `pubkey` is a native age X25519 key, the only kind that can sign the roster.
+pub fn is_x25519_pubkey(pubkey: &str) -> bool {
+    x25519::Recipient::from_str(pubkey).is_ok()
+}
+
+/// Lets age plugins talk to the user, for example to ask for a PIN or a touch of a
+/// hardware key. Without a terminal, plugins are told that no answer is available.
+#[derive(Clone, Copy)]
+pub struct PluginCallbacks;
+
+impl PluginCallbacks {
+    fn read_answer(prompt: &str) -> Option<String> {
+        if !io::stdin().is_terminal() {
+            return None;
+        }
+        eprint!("{prompt}");
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer).ok()?;
+        Some(answer.trim().to_string())
+    }
+}
+
+impl Callbacks for PluginCallbacks {
+    fn display_message(&self, message: &str) {
+        eprintln!("{message}");
+    }
+
+    fn confirm(&self, message: &str, yes_string: &str, no_string: Option<&str>) -> Option<bool> {
+        let choices = match no_string {
+            Some(no_string) => format!("{yes_string}/{no_string}"),
+            None => yes_string.to_string(),
+        };
+        let answer = Self::read_answer(&format!("{message} [{choices}]: "))?;
+        Some(no_string.is_none_or(|no_string| !answer.eq_ignore_ascii_case(no_string)))
+    }
+
+    fn request_public_string(&self, description: &str) -> Option<String> {
+        Self::read_answer(&format!("{description}: "))
+    }
+
+    fn request_passphrase(&self, description: &str) -> Option<SecretString> {
+        if !io::stdin().is_terminal() {
+            return None;
+        }
+        rpassword::prompt_password(format!("{description}: ")).ok().map(SecretString::from)
+    }
+}
+
+fn ssh_recipient_error(err: ParseRecipientKeyError) -> EnvkeyError {
+    EnvkeyError::message(match err {
+        ParseRecipientKeyError::Ignore | ParseRecipientKeyError::Invalid(_) => {
+            "invalid SSH public key".to_string()
+        }
+        ParseRecipientKeyError::RsaModulusTooLarge => {
+            "ssh-rsa keys larger than 4096 bits are not supported".to_string()
+        }
+        ParseRecipientKeyError::RsaModulusTooSmall => {
+            "ssh-rsa keys smaller than 2048 bits are not supported".to_string()
+        }
+        ParseRecipientKeyError::Unsupported(key_type) => {
+            format!("unsupported SSH key type {key_type}")
+        }
+    })
+}
+
+pub fn encrypt_value(plaintext: &str, recipients: &[Box<dyn Recipient>]) -> Result<String> {
+    encrypt_bytes(plaintext.as_bytes(), recipients)
+}
+
+pub fn encrypt_bytes(plaintext: &[u8], recipients: &[Box<dyn Recipient>]) -> Result<String> {
     if recipients.is_empty() {
         return Err(EnvkeyError::message("cannot encrypt without at least one recipient"));
     }
 
-    let encryptor = Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn Recipient))
+    let encryptor = Encryptor::with_recipients(recipients.iter().map(|r| r.as_ref()))
         .map_err(|err| EnvkeyError::message(format!("failed to build encryptor: {err}")))?;
 
     let mut out = Vec::new();
@@ -19,7 +148,7 @@ pub fn encrypt_value(plaintext: &str, recipients: &[x25519::Recipient]) -> Resul
         .wrap_output(&mut out)
         .map_err(|err| EnvkeyError::message(format!("failed to wrap encrypt output: {err}")))?;
     writer
-        .write_all(plaintext.as_bytes())
+        .write_all(plaintext)
         .map_err(|err| EnvkeyError::message(format!("failed to encrypt value: {err}")))?;
     writer
         .finish()
@@ -28,21 +157,105 @@ pub fn encrypt_value(plaintext: &str, recipients: &[x25519::Recipient]) -> Resul
     Ok(STANDARD.encode(out))
 }
 
-pub fn decrypt_value(ciphertext_b64: &str, identity: &x25519::Identity) -> Result<String> {
+pub fn decrypt_value(ciphertext_b64: &str, identity: &dyn Identity) -> Result<String> {
+    let decrypted = decrypt_bytes(ciphertext_b64, identity)?;
+
+    String::from_utf8(decrypted)
+        .map_err(|err| EnvkeyError::message(format!("decrypted value is not valid UTF-8: {err}")))
+}
+
+pub fn decrypt_bytes(ciphertext_b64: &str, identity: &dyn Identity) -> Result<Vec<u8>> {
     let ciphertext = STANDARD
         .decode(ciphertext_b64)
         .map_err(|err| EnvkeyError::message(format!("ciphertext is not valid base64: {err}")))?;
 
-    let decrypted = decrypt(identity, &ciphertext)
-        .map_err(|err| EnvkeyError::message(format!("failed to decrypt value: {err}")))?;
+    let decrypt_error = |err: &dyn std::fmt::Display| {
+        EnvkeyError::message(format!("failed to decrypt value: {err}"))
+    };
+    let mut reader = Decryptor::new_buffered(ciphertext.as_slice())
+        .and_then(|decryptor| decryptor.decrypt(iter::once(identity)))
+        .map_err(|err| decrypt_error(&err))?;
+    let mut plaintext = Vec::new();
+    reader.read_to_end(&mut plaintext).map_err(|err| decrypt_error(&err))?;
+    Ok(plaintext)
+}
 
-    String::from_utf8(decrypted)
-        .map_err(|err| EnvkeyError::message(format!("decrypted value is not valid UTF-8: {err}")))
+/// Signs `message` with an age X25519 identity using XEdDSA, so the signature can be
+/// checked against the signer's `age1...` public key without a separate signing key.
+pub fn sign_message(identity: &x25519::Identity, message: &[u8]) -> Result<String> {
+    let encoded = identity.to_string();
+    let secret = Zeroizing::new(decode_age_key(encoded.expose_secret(), "age-secret-key-")?);
+    let k = Scalar::from_bytes_mod_order(clamp_integer(*secret));
+
+    // XEdDSA fixes the sign bit of the Edwards public key to zero, negating the scalar
+    // when needed, so the key can be recovered from the Montgomery form alone.
+    let mut public = EdwardsPoint::mul_base(&k).compress().to_bytes();
+    let a = if public[31] & 0x80 != 0 { -k } else { k };
+    public[31] &= 0x7f;
+
+    let mut nonce = Zeroizing::new([0u8; 64]);
+    rng().fill(&mut nonce[..]);
+    let r = hash_to_scalar(&[&XEDDSA_NONCE_PREFIX, a.as_bytes(), message, &nonce[..]]);
+    let big_r = EdwardsPoint::mul_base(&r).compress().to_bytes();
+    let h = hash_to_scalar(&[&big_r, &public, message]);
+    let s = r + h * a;
+
+    let mut signature = [0u8; 64];
+    signature[..32].copy_from_slice(&big_r);
+    signature[32..].copy_from_slice(s.as_bytes());
+    Ok(STANDARD.encode(signature))
+}
+
+/// Checks an XEdDSA signature produced by [`sign_message`] against an `age1...` public key.
+pub fn verify_signature(pubkey: &str, message: &[u8], signature_b64: &str) -> bool {
+    let Ok(u) = decode_age_key(pubkey, "age") else {
+        return false;
+    };
+    let Some(public) = MontgomeryPoint(u).to_edwards(0) else {
+        return false;
+    };
+    if public.is_small_order() {
+        return false;
+    }
+    let Ok(signature) = STANDARD.decode(signature_b64) else {
+        return false;
+    };
+    let Ok(signature) = <[u8; 64]>::try_from(signature) else {
+        return false;
+    };
+
+    let big_r = &signature[..32];
+    let s_bytes: [u8; 32] = signature[32..].try_into().expect("32-byte half");
+    let Some(s) = Option::<Scalar>::from(Scalar::from_canonical_bytes(s_bytes)) else {
+        return false;
+    };
+    let h = hash_to_scalar(&[big_r, public.compress().as_bytes(), message]);
+    let check = EdwardsPoint::vartime_double_scalar_mul_basepoint(&-h, &public, &s);
+    check.compress().as_bytes() == big_r
+}
+
+fn hash_to_scalar(parts: &[&[u8]]) -> Scalar {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    Scalar::from_bytes_mod_order_wide(&hasher.finalize().into())
+}
+
+fn decode_age_key(encoded: &str, expected_hrp: &str) -> Result<[u8; 32]> {
+    let invalid = || EnvkeyError::message("invalid age key encoding");
+    let (hrp, data, _) = bech32::decode(encoded).map_err(|_| invalid())?;
+    if hrp != expected_hrp {
+        return Err(invalid());
+    }
+    let bytes = Zeroizing::new(Vec::<u8>::from_base32(&data).map_err(|_| invalid())?);
+    bytes.as_slice().try_into().map_err(|_| invalid())
 }
 
 #[cfg(test)]
 mod tests {
     use age::x25519;
+    use bech32::ToBase32;
 
     use super::*;
 
@@ -51,19 +264,80 @@ mod tests {
         let identity = x25519::Identity::generate();
         let recipient = identity.to_public();
 
-        let encrypted = encrypt_value("super-secret", &[recipient]).expect("encrypt");
+        let encrypted = encrypt_value("super-secret", &[Box::new(recipient)]).expect("encrypt");
         let decrypted = decrypt_value(&encrypted, &identity).expect("decrypt");
 
         assert_eq!(decrypted, "super-secret");
     }
 
+    #[test]
+    fn binary_round_trip_and_utf8_guard() {
+        let identity = x25519::Identity::generate();
+        let payload = [0u8, 159, 146, 150, 255];
+
+        let encrypted =
+            encrypt_bytes(&payload, &[Box::new(identity.to_public())]).expect("encrypt");
+        assert_eq!(decrypt_bytes(&encrypted, &identity).expect("decrypt"), payload);
+
+        let err = decrypt_value(&encrypted, &identity).expect_err("must fail");
+        assert!(err.to_string().contains("decrypted value is not valid UTF-8"));
+    }
+
+    #[test]
+    fn ssh_public_keys_parse_as_recipients_without_comments() {
+        let ed25519 =
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIFPWB8oURx6iUS5DjY1oqSnoUycBvGOqHwUtUFutMkqd";
+        let rsa = "ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQCbW4FzDds5J4fI9QTTo40aCuDnzn4VdKr4K1Ez1k+gWJHSbAh5j9W60OOV+1SsuEEYPSzvAVaDEymgaMyeDght3WYwB0Hk8dVcNKOWutpvi/3Kj9Iigf0HdKu99fnImnM7YMv/izXfNM/uyOA8P5DvXTqTHe1xIcSiE3qT8ntdIC5agS9iWJ0EGJ+Hiww1eU8j/IUgDSwa7JuqpX92DpS4sOmJ988PLQ9FYUUaSOxOfysrcvDBy6gcoqVH1ovo+RzgDlUk/8DVlX1cGRXY+EsS/+JOdOTvE1Fq9WXxbK255t/RqXyFPmwOrx2vzSQKDXemOCsVmNWZdql6HKFuXBHJ";
+
+        assert_eq!(
+            canonical_pubkey(&format!("{ed25519} alice@laptop\n")).expect("ed25519"),
+            ed25519
+        );
+        assert_eq!(canonical_pubkey(rsa).expect("rsa"), rsa);
+
+        let recipients =
+            [parse_recipient(ed25519).expect("ed25519"), parse_recipient(rsa).expect("rsa")];
+        encrypt_value("super-secret", &recipients).expect("encrypt");
+
+        let err = parse_recipient("ssh-dss AAAAB3NzaC1kc3MAAACBAP").err().expect("must fail");
+        assert!(err.to_string().contains("SSH"));
+    }
+
+    #[test]
+    fn plugin_recipients_need_their_plugin_binary() {
+        let recipient =
+            bech32::encode("age1missing", [7u8; 32].to_base32(), bech32::Variant::Bech32)
+                .expect("bech32");
+
+        assert_eq!(canonical_pubkey(&recipient).expect("canonical"), recipient);
+        assert!(!is_x25519_pubkey(&recipient));
+        assert!(is_x25519_pubkey(&x25519::Identity::generate().to_public().to_string()));
+
+        let err = parse_recipient(&recipient).err().expect("no plugin binary");
+        assert!(err.to_string().contains("age-plugin-missing"));
+    }
+
+    #[test]
+    fn signatures_verify_against_age_public_key() {
+        let identity = x25519::Identity::generate();
+        let other = x25519::Identity::generate();
+        let pubkey = identity.to_public().to_string();
+
+        let signature = sign_message(&identity, b"roster entry").expect("sign");
+
+        assert!(verify_signature(&pubkey, b"roster entry", &signature));
+        assert!(!verify_signature(&pubkey, b"tampered entry", &signature));
+        assert!(!verify_signature(&other.to_public().to_string(), b"roster entry", &signature));
+        assert!(!verify_signature(&pubkey, b"roster entry", "not-a-signature"));
+    }
+
     #[test]
     fn decrypt_with_wrong_identity_fails() {
         let identity_a = x25519::Identity::generate();
         let identity_b = x25519::Identity::generate();
         let recipient = identity_a.to_public();
 
-        let encrypted = encrypt_value("super-secret", &[recipient]).expect("encrypt");
+        let encrypted = encrypt_value("super-secret", &[Box::new(recipient)]).expect("encrypt");
         let err = decrypt_value(&encrypted, &identity_b).expect_err("must fail");
 
         assert!(err.to_string().contains("failed to decrypt value"));
@@ -0,0 +1,205 @@
+use crate::error::{EnvkeyError, Result};
+
+/// Characters that never need quoting in a dotenv value.
+fn is_bare_char(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || matches!(c, '_' | '.' | '/' | ':' | '@' | '%' | '+' | ',' | '=' | '-')
+}
+
+/// Renders a value so that dotenv parsers read back exactly the same string.
+///
+/// Simple values are written bare; anything else is double-quoted with `\`, `"`, `$`
+/// and line breaks escaped, which keeps multiline values on a single line.
+pub fn quote(value: &str) -> String {
+    if !value.is_empty() && value.chars().all(is_bare_char) {
+        return value.to_string();
+    }
+
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '$' => out.push_str("\\$"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Parses dotenv content into key/value pairs in file order.
+///
+/// Supports `#` comments, an optional `export` prefix, bare values with trailing
+/// comments, single-quoted literals, and double-quoted values with escapes. Quoted
+/// values may span several lines.
+pub fn parse(input: &str) -> Result<Vec<(String, String)>> {
+    let lines: Vec<&str> = input.lines().collect();
+    let mut entries = Vec::new();
+    let mut index = 0;
+
+    while index < lines.len() {
+        let line_no = index + 1;
+        let line = lines[index].trim_start();
+        index += 1;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = strip_export_prefix(line);
+        let (key, raw_value) =
+            line.split_once('=').ok_or_else(|| parse_error(line_no, "expected KEY=VALUE"))?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(parse_error(line_no, "missing key before `=`"));
+        }
+
+        let raw_value = raw_value.trim_start();
+        let value = match raw_value.chars().next() {
+            Some(quote @ ('\'' | '"')) => {
+                let mut buffer = raw_value[1..].to_string();
+                loop {
+                    if let Some((value, rest)) = scan_quoted(&buffer, quote) {
+                        let rest = rest.trim_start();
+                        if !rest.is_empty() && !rest.starts_with('#') {
+                            return Err(parse_error(
+                                line_no,
+                                "unexpected characters after closing quote",
+                            ));
+                        }
+                        break value;
+                    }
+                    let next = lines.get(index).ok_or_else(|| {
+                        parse_error(line_no, &format!("unterminated {quote}-quoted value"))
+                    })?;
+                    index += 1;
+                    buffer.push('\n');
+                    buffer.push_str(next);
+                }
+            }
+            _ => strip_inline_comment(raw_value).trim_end().to_string(),
+        };
+
+        entries.push((key.to_string(), value));
+    }
+
+    Ok(entries)
+}
+
+fn strip_export_prefix(line: &str) -> &str {
+    match line.strip_prefix("export") {
+        Some(rest) if rest.starts_with([' ', '\t']) => rest.trim_start(),
+        _ => line,
+    }
+}
+
+fn strip_inline_comment(value: &str) -> &str {
+    let mut previous = ' ';
+    for (pos, c) in value.char_indices() {
+        if c == '#' && previous.is_whitespace() {
+            return &value[..pos];
+        }
+        previous = c;
+    }
+    value
+}
+
+/// Returns the unescaped value and the text after the closing quote, or `None`
+/// when the closing quote has not been reached yet.
+fn scan_quoted(buffer: &str, quote: char) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = buffer.char_indices();
+    while let Some((pos, c)) = chars.next() {
+        if c == quote {
+            return Some((value, &buffer[pos + 1..]));
+        }
+        if c == '\\' && quote == '"' {
+            let (_, escaped) = chars.next()?;
+            match escaped {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                '\\' | '"' | '$' | '`' => value.push(escaped),
+                other => {
+                    value.push('\\');
+                    value.push(other);
+                }
+            }
+            continue;
+        }
+        value.push(c);
+    }
+    None
+}
+
+fn parse_error(line_no: usize, message: &str) -> EnvkeyError {
+    EnvkeyError::message(format!("invalid dotenv syntax on line {line_no}: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simple_values_are_bare() {
+        assert_eq!(
+            quote("postgres://user@localhost:5432/app"),
+            "postgres://user@localhost:5432/app"
+        );
+    }
+
+    #[test]
+    fn special_values_are_quoted_and_escaped() {
+        assert_eq!(quote(""), "\"\"");
+        assert_eq!(quote("two words"), "\"two words\"");
+        assert_eq!(quote("line1\nline2"), "\"line1\\nline2\"");
+        assert_eq!(quote("say \"hi\" $USER \\o/"), "\"say \\\"hi\\\" \\$USER \\\\o/\"");
+    }
+
+    #[test]
+    fn parses_comments_exports_and_bare_values() {
+        let input = "# header\n\nexport API_KEY=abc123\nURL = https://example.com/#anchor # trailing\nEMPTY=\n";
+        let entries = parse(input).expect("parse");
+        assert_eq!(
+            entries,
+            vec![
+                ("API_KEY".to_string(), "abc123".to_string()),
+                ("URL".to_string(), "https://example.com/#anchor".to_string()),
+                ("EMPTY".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_quoted_and_multiline_values() {
+        let input = "SINGLE='raw \\n $HOME' # note\nDOUBLE=\"a\\tb\\n\\\"c\\\"\"\nCERT=\"-----BEGIN-----\nabc\n-----END-----\"\nNEXT=1\n";
+        let entries = parse(input).expect("parse");
+        assert_eq!(entries[0].1, "raw \\n $HOME");
+        assert_eq!(entries[1].1, "a\tb\n\"c\"");
+        assert_eq!(entries[2].1, "-----BEGIN-----\nabc\n-----END-----");
+        assert_eq!(entries[3], ("NEXT".to_string(), "1".to_string()));
+    }
+
+    #[test]
+    fn quoted_values_round_trip() {
+        let original = "multi\nline \"quoted\" $VAR \\ tab\t";
+        let entries = parse(&format!("KEY={}\n", quote(original))).expect("parse");
+        assert_eq!(entries[0].1, original);
+    }
+
+    #[test]
+    fn reports_line_numbers_for_errors() {
+        let err = parse("A=1\nnot a pair\n").expect_err("must fail");
+        assert!(err.to_string().contains("line 2: expected KEY=VALUE"));
+
+        let err = parse("A=1\nB=\"open\nstill open\n").expect_err("must fail");
+        assert!(err.to_string().contains("line 2: unterminated \"-quoted value"));
+
+        let err = parse("A='x' y\n").expect_err("must fail");
+        assert!(err.to_string().contains("unexpected characters after closing quote"));
+    }
+}
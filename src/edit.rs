@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use rand::distr::Alphanumeric;
+use rand::{Rng, rng};
+use secrecy::{ExposeSecret, SecretString};
+
+use crate::error::{EnvkeyError, Result};
+
+/// A scratch directory only the current user can enter. Files inside it are overwritten
+/// with zeros before the directory is removed, including when an edit is aborted.
+pub struct PrivateDir {
+    path: PathBuf,
+}
+
+impl PrivateDir {
+    pub fn create() -> Result<Self> {
+        let suffix: String = rng().sample_iter(Alphanumeric).map(char::from).take(12).collect();
+        let path = env::temp_dir().join(format!("envkey-edit-{suffix}"));
+
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+
+            builder.mode(0o700);
+        }
+        builder.create(&path).map_err(|err| {
+            EnvkeyError::message(format!(
+                "failed to create private directory {}: {err}",
+                path.display()
+            ))
+        })?;
+
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PrivateDir {
+    fn drop(&mut self) {
+        if let Ok(entries) = fs::read_dir(&self.path) {
+            for entry in entries.flatten() {
+                let _ = wipe_file(&entry.path());
+            }
+        }
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Overwrites a file with zeros and flushes it to disk before unlinking it, so editor
+/// buffers do not leave plaintext behind in freed blocks.
+fn wipe_file(path: &Path) -> io::Result<()> {
+    let len = fs::metadata(path)?.len();
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    io::copy(&mut io::repeat(0).take(len), &mut file)?;
+    file.flush()?;
+    file.sync_all()?;
+    drop(file);
+    fs::remove_file(path)
+}
+
+/// Opens `path` in `$VISUAL` or `$EDITOR` (falling back to `vi`) and waits for it to exit.
+/// The editor setting may carry arguments, as in `code --wait`.
+pub fn open_editor(path: &Path) -> Result<()> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().expect("editor is not empty");
+
+    let status =
+        Command::new(program).args(parts).arg(path).status().map_err(|err| {
+            EnvkeyError::message(format!("failed to run editor {program}: {err}"))
+        })?;
+    if !status.success() {
+        return Err(EnvkeyError::message(format!(
+            "editor {program} exited with {status}; no changes written"
+        )));
+    }
+    Ok(())
+}
+
+const ERROR_COMMENT: &str = "# error: ";
+
+/// Puts `err` in a comment on the first line of an edited file about to be reopened,
+/// replacing the one left by a previous attempt.
+pub fn with_error_comment(edited: &str, err: &str) -> String {
+    let rest = match edited.strip_prefix(ERROR_COMMENT) {
+        Some(previous) => previous.split_once('\n').map_or("", |(_, rest)| rest),
+        None => edited,
+    };
+    format!("{ERROR_COMMENT}{}\n{rest}", err.replace('\n', " "))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditChange {
+    Added,
+    Changed,
+    Removed,
+}
+
+impl EditChange {
+    pub fn label(self) -> &'static str {
+        match self {
+            EditChange::Added => "added",
+            EditChange::Changed => "changed",
+            EditChange::Removed => "removed",
+        }
+    }
+}
+
+/// Compares the values written to the editor with the ones read back, by key.
+pub fn changes(
+    before: &BTreeMap<String, SecretString>,
+    after: &BTreeMap<String, SecretString>,
+) -> Vec<(String, EditChange)> {
+    let mut changes = Vec::new();
+    for (key, value) in after {
+        match before.get(key) {
+            None => changes.push((key.clone(), EditChange::Added)),
+            Some(old) if old.expose_secret() != value.expose_secret() => {
+                changes.push((key.clone(), EditChange::Changed));
+            }
+            Some(_) => {}
+        }
+    }
+    for key in before.keys().filter(|key| !after.contains_key(*key)) {
+        changes.push((key.clone(), EditChange::Removed));
+    }
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets(entries: &[(&str, &str)]) -> BTreeMap<String, SecretString> {
+        entries.iter().map(|(key, value)| (key.to_string(), value.to_string().into())).collect()
+    }
+
+    #[test]
+    fn changes_report_added_changed_and_removed_keys() {
+        let before = secrets(&[("A", "1"), ("B", "2"), ("C", "3")]);
+        let after = secrets(&[("A", "1"), ("B", "two"), ("D", "4")]);
+
+        assert_eq!(
+            changes(&before, &after),
+            [
+                ("B".to_string(), EditChange::Changed),
+                ("C".to_string(), EditChange::Removed),
+                ("D".to_string(), EditChange::Added),
+            ]
+        );
+        assert!(changes(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn error_comment_replaces_the_previous_one() {
+        let first = with_error_comment("A=1\nnot a line\n", "line 2: missing `=`");
+        assert_eq!(first, "# error: line 2: missing `=`\nA=1\nnot a line\n");
+
+        let second = with_error_comment(&first, "line 3: invalid key");
+        assert_eq!(second, "# error: line 3: invalid key\nA=1\nnot a line\n");
+    }
+
+    #[test]
+    fn private_dir_is_owner_only_and_wiped_on_drop() {
+        let dir = PrivateDir::create().expect("private dir");
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("secrets.env"), "A=1\n").expect("write");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = fs::metadata(&path).expect("metadata").permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+
+        drop(dir);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,196 @@
+use age::{Identity, Recipient};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand::{Rng, rng};
+use secrecy::{ExposeSecret, SecretSlice};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::crypto::{decrypt_bytes, encrypt_bytes};
+use crate::error::{EnvkeyError, Result};
+use crate::model::EnvkeyFile;
+
+const DATA_KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+
+/// Symmetric key that encrypts every secret in one environment. `.envkey` stores it
+/// age-encrypted to the environment's recipients, so membership changes only rewrap it.
+pub struct DataKey {
+    env_name: String,
+    key: SecretSlice<u8>,
+}
+
+impl DataKey {
+    pub fn generate(env_name: &str) -> Self {
+        let mut key = vec![0u8; DATA_KEY_LEN];
+        rng().fill(key.as_mut_slice());
+        Self { env_name: env_name.to_string(), key: key.into() }
+    }
+
+    pub fn unwrap(env_name: &str, wrapped: &str, identity: &dyn Identity) -> Result<Self> {
+        let key: SecretSlice<u8> = decrypt_bytes(wrapped, identity)
+            .map_err(|err| {
+                EnvkeyError::message(format!(
+                    "cannot unlock the data key for environment {env_name}: {err}"
+                ))
+            })?
+            .into();
+        if key.expose_secret().len() != DATA_KEY_LEN {
+            return Err(EnvkeyError::message(format!(
+                "invalid data key for environment {env_name}"
+            )));
+        }
+        Ok(Self { env_name: env_name.to_string(), key })
+    }
+
+    pub fn wrap(&self, recipients: &[Box<dyn Recipient>]) -> Result<String> {
+        encrypt_bytes(self.key.expose_secret(), recipients)
+    }
+
+    /// Encrypts a value with XChaCha20-Poly1305, bound to its environment and key name
+    /// so ciphertexts cannot be swapped between entries.
+    pub fn seal(&self, key: &str, plaintext: &[u8]) -> Result<String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rng().fill(&mut nonce);
+        let aad = self.aad(key);
+        let ciphertext = self
+            .cipher()
+            .encrypt(XNonce::from_slice(&nonce), Payload { msg: plaintext, aad: aad.as_bytes() })
+            .map_err(|_| EnvkeyError::message(format!("failed to encrypt {key}")))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(STANDARD.encode(sealed))
+    }
+
+    pub fn open(&self, key: &str, sealed_b64: &str) -> Result<Vec<u8>> {
+        let sealed = STANDARD.decode(sealed_b64).map_err(|err| {
+            EnvkeyError::message(format!("ciphertext is not valid base64: {err}"))
+        })?;
+        if sealed.len() < NONCE_LEN {
+            return Err(EnvkeyError::message(format!(
+                "failed to decrypt value: {key} is truncated"
+            )));
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let aad = self.aad(key);
+        self.cipher()
+            .decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad: aad.as_bytes() })
+            .map_err(|_| {
+                EnvkeyError::message(format!(
+                    "failed to decrypt value: {key} in {} was modified or moved from another entry",
+                    self.env_name
+                ))
+            })
+    }
+
+    /// Short digest identifying the key, so rotations show up without revealing it.
+    pub fn fingerprint(&self) -> String {
+        digest_hex(self.key.expose_secret())
+    }
+
+    /// Digest of a value under a key derived from this data key, so diffs show which values
+    /// changed without letting anyone who lacks the data key confirm a guess at them.
+    pub fn value_digest(&self, key: &str, plaintext: &[u8]) -> String {
+        let mut digest_key = Zeroizing::new([0u8; DATA_KEY_LEN]);
+        Hkdf::<Sha256>::new(None, self.key.expose_secret())
+            .expand(b"envkey:v2:value-digest", digest_key.as_mut_slice())
+            .expect("HKDF output length is valid");
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(digest_key.as_slice())
+            .expect("HMAC takes keys of any length");
+        mac.update(self.aad(key).as_bytes());
+        mac.update(&[0]);
+        mac.update(plaintext);
+        mac.finalize().into_bytes().iter().take(8).map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(Key::from_slice(self.key.expose_secret()))
+    }
+
+    fn aad(&self, key: &str) -> String {
+        format!("envkey:v2:{}:{key}", self.env_name)
+    }
+}
+
+/// First 16 hex digits of the SHA-256 of `bytes`.
+pub fn digest_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().take(8).map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Decrypts the values of one environment, unwrapping its data key once. Environments
+/// written before envelope encryption still hold one age ciphertext per value.
+pub enum EnvDecryptor<'a> {
+    Envelope(DataKey),
+    Legacy(&'a dyn Identity),
+}
+
+impl<'a> EnvDecryptor<'a> {
+    pub fn new(file: &EnvkeyFile, env_name: &str, identity: &'a dyn Identity) -> Result<Self> {
+        match file.environments.get(env_name).and_then(|env| env.data_key.as_deref()) {
+            Some(wrapped) => Ok(Self::Envelope(DataKey::unwrap(env_name, wrapped, identity)?)),
+            None => Ok(Self::Legacy(identity)),
+        }
+    }
+
+    pub fn data_key(&self) -> Option<&DataKey> {
+        match self {
+            Self::Envelope(data_key) => Some(data_key),
+            Self::Legacy(_) => None,
+        }
+    }
+
+    pub fn decrypt(&self, key: &str, value: &str) -> Result<SecretSlice<u8>> {
+        let plaintext = match self {
+            Self::Envelope(data_key) => data_key.open(key, value)?,
+            Self::Legacy(identity) => decrypt_bytes(value, *identity)?,
+        };
+        Ok(plaintext.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use age::x25519;
+
+    use super::*;
+
+    #[test]
+    fn seal_open_round_trip_through_wrapped_key() {
+        let identity = x25519::Identity::generate();
+        let data_key = DataKey::generate("staging");
+        let wrapped = data_key.wrap(&[Box::new(identity.to_public())]).expect("wrap");
+        let sealed = data_key.seal("API_KEY", b"secret").expect("seal");
+
+        let unwrapped = DataKey::unwrap("staging", &wrapped, &identity).expect("unwrap");
+        assert_eq!(unwrapped.open("API_KEY", &sealed).expect("open"), b"secret");
+    }
+
+    #[test]
+    fn sealed_values_are_bound_to_environment_and_key() {
+        let data_key = DataKey::generate("staging");
+        let sealed = data_key.seal("API_KEY", b"secret").expect("seal");
+
+        let err = data_key.open("OTHER_KEY", &sealed).expect_err("must fail");
+        assert!(err.to_string().contains("modified or moved"));
+
+        let moved = DataKey { env_name: "production".to_string(), key: data_key.key.clone() };
+        assert!(moved.open("API_KEY", &sealed).is_err());
+    }
+
+    #[test]
+    fn value_digests_are_keyed_by_the_data_key() {
+        let data_key = DataKey::generate("staging");
+        let digest = data_key.value_digest("API_KEY", b"secret");
+
+        assert_eq!(digest, data_key.value_digest("API_KEY", b"secret"));
+        assert_ne!(digest, data_key.value_digest("API_KEY", b"other"));
+        assert_ne!(digest, DataKey::generate("staging").value_digest("API_KEY", b"secret"));
+        assert_ne!(digest, digest_hex(b"secret"));
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+
+use rand::distr::Alphanumeric;
+use rand::{Rng, rng};
+use secrecy::{ExposeSecret, SecretString};
+
+use crate::dotenv;
+use crate::error::{EnvkeyError, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Dotenv,
+    Shell,
+    Json,
+    Yaml,
+    GithubEnv,
+}
+
+pub fn render(format: ExportFormat, secrets: &BTreeMap<String, SecretString>) -> Result<String> {
+    match format {
+        ExportFormat::Dotenv => Ok(secrets
+            .iter()
+            .map(|(key, value)| format!("{key}={}\n", dotenv::quote(value.expose_secret())))
+            .collect()),
+        ExportFormat::Shell => Ok(secrets
+            .iter()
+            .map(|(key, value)| format!("export {key}={}\n", shell_quote(value.expose_secret())))
+            .collect()),
+        ExportFormat::Json => {
+            let mut json = serde_json::to_string_pretty(&plain_map(secrets)).map_err(|err| {
+                EnvkeyError::message(format!("failed to serialize JSON export: {err}"))
+            })?;
+            json.push('\n');
+            Ok(json)
+        }
+        ExportFormat::Yaml => {
+            if secrets.is_empty() {
+                return Ok("{}\n".to_string());
+            }
+            serde_yaml::to_string(&plain_map(secrets)).map_err(|err| {
+                EnvkeyError::message(format!("failed to serialize YAML export: {err}"))
+            })
+        }
+        ExportFormat::GithubEnv => Ok(secrets
+            .iter()
+            .map(|(key, value)| github_env_entry(key, value.expose_secret()))
+            .collect()),
+    }
+}
+
+fn plain_map(secrets: &BTreeMap<String, SecretString>) -> BTreeMap<&str, &str> {
+    secrets.iter().map(|(key, value)| (key.as_str(), value.expose_secret())).collect()
+}
+
+/// Single-quotes a value for POSIX shells; embedded quotes become `'\''`.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Uses the `KEY<<DELIMITER` form GitHub Actions accepts for multiline values,
+/// with a random delimiter that cannot appear inside the value.
+fn github_env_entry(key: &str, value: &str) -> String {
+    let delimiter = loop {
+        let suffix: String = rng().sample_iter(Alphanumeric).map(char::from).take(16).collect();
+        let candidate = format!("ENVKEY_EOF_{suffix}");
+        if !value.contains(&candidate) {
+            break candidate;
+        }
+    };
+    format!("{key}<<{delimiter}\n{value}\n{delimiter}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets(pairs: &[(&str, &str)]) -> BTreeMap<String, SecretString> {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.to_string().into())).collect()
+    }
+
+    #[test]
+    fn renders_dotenv_with_quoting() {
+        let out = render(ExportFormat::Dotenv, &secrets(&[("A", "plain"), ("B", "x\ny")]))
+            .expect("render");
+        assert_eq!(out, "A=plain\nB=\"x\\ny\"\n");
+    }
+
+    #[test]
+    fn renders_shell_exports_with_single_quotes() {
+        let out = render(ExportFormat::Shell, &secrets(&[("A", "it's $HOME")])).expect("render");
+        assert_eq!(out, "export A='it'\\''s $HOME'\n");
+    }
+
+    #[test]
+    fn renders_json_and_yaml_maps() {
+        let pairs = secrets(&[("A", "1"), ("B", "two\nlines")]);
+
+        let json: BTreeMap<String, String> =
+            serde_json::from_str(&render(ExportFormat::Json, &pairs).expect("json"))
+                .expect("parse");
+        assert_eq!(json["B"], "two\nlines");
+
+        let yaml: BTreeMap<String, String> =
+            serde_yaml::from_str(&render(ExportFormat::Yaml, &pairs).expect("yaml"))
+                .expect("parse");
+        assert_eq!(yaml["A"], "1");
+    }
+
+    #[test]
+    fn renders_github_env_heredocs() {
+        let out = render(ExportFormat::GithubEnv, &secrets(&[("A", "x\ny")])).expect("render");
+        let mut lines = out.lines();
+        let header = lines.next().expect("header");
+        let delimiter = header.strip_prefix("A<<").expect("heredoc header");
+        assert!(delimiter.starts_with("ENVKEY_EOF_"));
+        assert_eq!(lines.collect::<Vec<_>>(), vec!["x", "y", delimiter]);
+    }
+}
@@ -0,0 +1,142 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{EnvkeyError, Result};
+
+/// `.gitattributes` pattern covering `.envkey` and named files such as `prod.envkey`.
+pub const ATTRIBUTES_PATTERN: &str = "*.envkey";
+
+/// Attributes `envkey git install` sets for [`ATTRIBUTES_PATTERN`].
+const ATTRIBUTES: &[&str] = &["merge=envkey", "diff=envkey"];
+
+/// Local git config entries that define the drivers named in [`ATTRIBUTES`]. Textconv
+/// caching stays off so decrypted digests never land in git notes.
+const CONFIG: &[(&str, &str)] = &[
+    ("merge.envkey.name", "envkey structural merge"),
+    ("merge.envkey.driver", "envkey merge-driver %O %A %B"),
+    ("diff.envkey.textconv", "envkey git diff-textconv"),
+    ("diff.envkey.cachetextconv", "false"),
+];
+
+pub fn run_git(cwd: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(cwd)
+        .output()
+        .map_err(|err| EnvkeyError::message(format!("failed to run git: {err}")))?;
+    if !output.status.success() {
+        return Err(EnvkeyError::message(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|err| EnvkeyError::message(format!("git output is not valid UTF-8: {err}")))
+}
+
+pub fn repo_root(cwd: &Path) -> Result<PathBuf> {
+    let root = run_git(cwd, &["rev-parse", "--show-toplevel"]).map_err(|_| {
+        EnvkeyError::message(format!("{} is not inside a git repository", cwd.display()))
+    })?;
+    Ok(PathBuf::from(root.trim_end()))
+}
+
+/// Returns the content of `path` as committed at `rev`.
+pub fn show_at_revision(path: &Path, rev: &str) -> Result<String> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .canonicalize()?;
+    let root = repo_root(&dir)?.canonicalize()?;
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| EnvkeyError::message(format!("{} is not a file path", path.display())))?;
+    let relative = dir.join(file_name);
+    let relative = relative.strip_prefix(&root).map_err(|_| {
+        EnvkeyError::message(format!("{} is outside the git repository", path.display()))
+    })?;
+    let spec: Vec<String> =
+        relative.components().map(|part| part.as_os_str().to_string_lossy().into_owned()).collect();
+
+    run_git(&root, &["show", &format!("{rev}:{}", spec.join("/"))])
+}
+
+/// Registers the envkey drivers in the repository at `root`. Returns the `.gitattributes`
+/// line when it had to be added or extended.
+pub fn install(root: &Path) -> Result<Option<String>> {
+    for (key, value) in CONFIG {
+        run_git(root, &["config", "--local", key, value])?;
+    }
+    ensure_attributes(&root.join(".gitattributes"))
+}
+
+fn ensure_attributes(path: &Path) -> Result<Option<String>> {
+    let existing = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(EnvkeyError::message(format!("failed to read {}: {err}", path.display())));
+        }
+    };
+
+    let mut lines: Vec<String> = existing.lines().map(str::to_string).collect();
+    let position =
+        lines.iter().position(|line| line.split_whitespace().next() == Some(ATTRIBUTES_PATTERN));
+    let line = match position {
+        Some(index) => &mut lines[index],
+        None => {
+            lines.push(ATTRIBUTES_PATTERN.to_string());
+            lines.last_mut().expect("line was just pushed")
+        }
+    };
+
+    let missing: Vec<&str> = ATTRIBUTES
+        .iter()
+        .copied()
+        .filter(|attribute| !line.split_whitespace().skip(1).any(|existing| existing == *attribute))
+        .collect();
+    if missing.is_empty() {
+        return Ok(None);
+    }
+    line.push(' ');
+    line.push_str(&missing.join(" "));
+    let added = line.clone();
+
+    let mut content = lines.join("\n");
+    content.push('\n');
+    fs::write(path, content).map_err(|err| {
+        EnvkeyError::message(format!("failed to write {}: {err}", path.display()))
+    })?;
+    Ok(Some(added))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn attributes_are_added_once_and_existing_lines_are_extended() {
+        let temp = tempdir().expect("tempdir");
+        let path = temp.path().join(".gitattributes");
+        fs::write(&path, "*.png binary\n*.envkey -text").expect("write");
+
+        assert_eq!(
+            ensure_attributes(&path).expect("ensure").as_deref(),
+            Some("*.envkey -text merge=envkey diff=envkey")
+        );
+        assert_eq!(
+            fs::read_to_string(&path).expect("read"),
+            "*.png binary\n*.envkey -text merge=envkey diff=envkey\n"
+        );
+        assert_eq!(ensure_attributes(&path).expect("ensure"), None);
+
+        fs::remove_file(&path).expect("remove");
+        ensure_attributes(&path).expect("ensure");
+        assert_eq!(fs::read_to_string(&path).expect("read"), "*.envkey merge=envkey diff=envkey\n");
+    }
+}
@@ -1,6 +1,16 @@
 pub mod cli;
 pub mod crypto;
+pub mod dotenv;
+pub mod edit;
+pub mod envelope;
 pub mod error;
+pub mod export;
+pub mod git;
 pub mod identity;
+pub mod merge;
 pub mod model;
+pub mod output;
+pub mod project;
+pub mod roster;
 pub mod storage;
+pub mod trust;
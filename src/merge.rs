@@ -0,0 +1,308 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::model::{Environment, EnvkeyFile, RosterSeal, SecretEntry, TeamMember};
+
+/// Outcome of a structural three-way merge of `.envkey` files. Conflicting entries keep
+/// our side so the merged file stays readable while the conflict is resolved.
+#[derive(Debug)]
+pub struct MergeResult {
+    pub file: EnvkeyFile,
+    pub conflicts: Vec<String>,
+    /// Environments whose merged secrets may be sealed under different data keys, or
+    /// whose data key no longer matches the merged roster. Fixing them needs an identity.
+    pub rekey: Vec<RekeyEnv>,
+    /// Both sides changed the team, so neither side's roster seal covers the merged one.
+    /// It keeps the higher of their seals until an admin seals it again.
+    pub reseal: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct RekeyEnv {
+    pub env_name: String,
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+    /// Keys whose merged entry came from their side, sealed under `theirs`.
+    pub from_theirs: Vec<String>,
+}
+
+enum Merged<T> {
+    Clean(Option<T>),
+    Conflict,
+}
+
+fn merge_entry<T: PartialEq + Clone>(
+    base: Option<&T>,
+    ours: Option<&T>,
+    theirs: Option<&T>,
+) -> Merged<T> {
+    if ours == theirs || theirs == base {
+        Merged::Clean(ours.cloned())
+    } else if ours == base {
+        Merged::Clean(theirs.cloned())
+    } else {
+        Merged::Conflict
+    }
+}
+
+fn union_keys<V>(maps: [&BTreeMap<String, V>; 3]) -> BTreeSet<&String> {
+    maps.iter().flat_map(|map| map.keys()).collect()
+}
+
+pub fn merge_files(base: &EnvkeyFile, ours: &EnvkeyFile, theirs: &EnvkeyFile) -> MergeResult {
+    let mut conflicts = Vec::new();
+    let mut rekey = Vec::new();
+
+    let mut team = BTreeMap::new();
+    for name in union_keys([&base.team, &ours.team, &theirs.team]) {
+        let (b, o, t) = (base.team.get(name), ours.team.get(name), theirs.team.get(name));
+        match merge_entry(b, o, t) {
+            Merged::Clean(member) => {
+                team.extend(member.map(|member| (name.clone(), member)));
+            }
+            Merged::Conflict => {
+                conflicts.push(describe_member_conflict(name, o, t));
+                team.extend(o.cloned().map(|member| (name.clone(), member)));
+            }
+        }
+    }
+
+    let empty = Environment::default();
+    let mut environments = BTreeMap::new();
+    for env_name in union_keys([&base.environments, &ours.environments, &theirs.environments]) {
+        let b = base.environments.get(env_name).unwrap_or(&empty);
+        let o = ours.environments.get(env_name).unwrap_or(&empty);
+        let t = theirs.environments.get(env_name).unwrap_or(&empty);
+
+        let mut secrets = BTreeMap::new();
+        for key in union_keys([&b.secrets, &o.secrets, &t.secrets]) {
+            let (be, oe, te) = (b.secrets.get(key), o.secrets.get(key), t.secrets.get(key));
+            match merge_entry(be, oe, te) {
+                Merged::Clean(entry) => {
+                    secrets.extend(entry.map(|entry| (key.clone(), entry)));
+                }
+                Merged::Conflict => {
+                    conflicts.push(describe_secret_conflict(env_name, key, oe, te));
+                    secrets.extend(oe.cloned().map(|entry| (key.clone(), entry)));
+                }
+            }
+        }
+
+        let from_theirs = differing_keys(&secrets, &o.secrets);
+        let from_ours = differing_keys(&secrets, &t.secrets);
+        let data_key =
+            if o.data_key == t.data_key || (t.data_key == b.data_key && from_theirs.is_empty()) {
+                o.data_key.clone()
+            } else if o.data_key == b.data_key && from_ours.is_empty() {
+                t.data_key.clone()
+            } else {
+                rekey.push(RekeyEnv {
+                    env_name: env_name.clone(),
+                    base: b.data_key.clone(),
+                    ours: o.data_key.clone(),
+                    theirs: t.data_key.clone(),
+                    from_theirs,
+                });
+                o.data_key.clone().or_else(|| t.data_key.clone())
+            };
+
+        if ours.environments.contains_key(env_name) || theirs.environments.contains_key(env_name) {
+            environments.insert(env_name.clone(), Environment { data_key, secrets });
+        }
+    }
+
+    // A seal covers exactly one side's team. When both sides changed it, the merged team
+    // keeps the higher seal, so resealing it lands above both serials.
+    fn sealed(file: &EnvkeyFile) -> (&BTreeMap<String, TeamMember>, &Option<RosterSeal>) {
+        (&file.team, &file.roster)
+    }
+    let (roster, reseal) =
+        match merge_entry(Some(&sealed(base)), Some(&sealed(ours)), Some(&sealed(theirs))) {
+            Merged::Clean(side) => (side.and_then(|(_, roster)| roster.clone()), false),
+            Merged::Conflict => {
+                let seals = [&ours.roster, &theirs.roster].into_iter().flatten();
+                (seals.max_by_key(|seal| seal.serial).cloned(), true)
+            }
+        };
+
+    let file = EnvkeyFile {
+        version: ours.version,
+        team,
+        roster,
+        environments,
+        migrated_from: None,
+        rolled_back_from: None,
+    };
+    MergeResult { file, conflicts, rekey, reseal }
+}
+
+fn differing_keys(
+    merged: &BTreeMap<String, SecretEntry>,
+    side: &BTreeMap<String, SecretEntry>,
+) -> Vec<String> {
+    merged
+        .iter()
+        .filter(|(key, entry)| side.get(*key) != Some(entry))
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+fn describe_member_conflict(
+    name: &str,
+    ours: Option<&TeamMember>,
+    theirs: Option<&TeamMember>,
+) -> String {
+    match (ours, theirs) {
+        (Some(_), Some(_)) => format!("team member {name} was changed on both sides"),
+        (Some(_), None) => {
+            format!("team member {name} was changed on our side and removed on theirs")
+        }
+        (None, _) => format!("team member {name} was removed on our side and changed on theirs"),
+    }
+}
+
+fn describe_secret_conflict(
+    env_name: &str,
+    key: &str,
+    ours: Option<&SecretEntry>,
+    theirs: Option<&SecretEntry>,
+) -> String {
+    let describe = |entry: Option<&SecretEntry>| match entry {
+        Some(entry) => format!("set by {} at {}", entry.set_by, entry.modified),
+        None => "removed".to_string(),
+    };
+    format!("{key} in {env_name}: ours {}, theirs {}", describe(ours), describe(theirs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(value: &str, set_by: &str, modified: &str) -> SecretEntry {
+        SecretEntry {
+            value: value.to_string(),
+            encoding: None,
+            set_by: set_by.to_string(),
+            modified: modified.to_string(),
+        }
+    }
+
+    fn seal(serial: u64) -> RosterSeal {
+        RosterSeal { serial, signed_by: "alice".to_string(), signature: format!("sig-{serial}") }
+    }
+
+    fn base_file() -> EnvkeyFile {
+        let mut file =
+            EnvkeyFile::new("alice".to_string(), "age1alice".to_string(), "2026-02-26".to_string());
+        let env = file.environments.get_mut("default").expect("default env");
+        env.data_key = Some("wrapped-1".to_string());
+        env.secrets.insert("SHARED".to_string(), entry("c0", "alice", "t0"));
+        file
+    }
+
+    #[test]
+    fn disjoint_keys_merge_cleanly() {
+        let base = base_file();
+        let mut ours = base.clone();
+        ours.env_mut("default").insert("OURS".to_string(), entry("c1", "alice", "t1"));
+        let mut theirs = base.clone();
+        theirs.env_mut("default").insert("THEIRS".to_string(), entry("c2", "bob", "t2"));
+
+        let result = merge_files(&base, &ours, &theirs);
+        assert!(result.conflicts.is_empty());
+        assert!(result.rekey.is_empty());
+        let merged = result.file.env("default").expect("default env");
+        assert_eq!(merged.keys().collect::<Vec<_>>(), ["OURS", "SHARED", "THEIRS"]);
+        assert_eq!(result.file.environments["default"].data_key.as_deref(), Some("wrapped-1"));
+    }
+
+    #[test]
+    fn same_key_changed_on_both_sides_is_reported_and_keeps_ours() {
+        let base = base_file();
+        let mut ours = base.clone();
+        ours.env_mut("default").insert("SHARED".to_string(), entry("c1", "alice", "t1"));
+        let mut theirs = base.clone();
+        theirs.env_mut("default").insert("SHARED".to_string(), entry("c2", "bob", "t2"));
+
+        let result = merge_files(&base, &ours, &theirs);
+        assert_eq!(
+            result.conflicts,
+            ["SHARED in default: ours set by alice at t1, theirs set by bob at t2"]
+        );
+        assert_eq!(result.file.env("default").expect("default env")["SHARED"].value, "c1");
+    }
+
+    #[test]
+    fn removal_on_one_side_applies_when_the_other_side_kept_the_entry() {
+        let base = base_file();
+        let mut ours = base.clone();
+        ours.env_mut("default").remove("SHARED");
+        let theirs = base.clone();
+
+        let result = merge_files(&base, &ours, &theirs);
+        assert!(result.conflicts.is_empty());
+        assert!(result.file.env("default").expect("default env").is_empty());
+    }
+
+    #[test]
+    fn data_key_change_with_secrets_from_the_other_side_needs_rekey() {
+        let base = base_file();
+        let mut ours = base.clone();
+        ours.environments.get_mut("default").expect("env").data_key = Some("wrapped-2".to_string());
+        let mut theirs = base.clone();
+        theirs.env_mut("default").insert("THEIRS".to_string(), entry("c2", "bob", "t2"));
+
+        let result = merge_files(&base, &ours, &theirs);
+        assert_eq!(
+            result.rekey,
+            [RekeyEnv {
+                env_name: "default".to_string(),
+                base: Some("wrapped-1".to_string()),
+                ours: Some("wrapped-2".to_string()),
+                theirs: Some("wrapped-1".to_string()),
+                from_theirs: vec!["THEIRS".to_string()],
+            }]
+        );
+
+        // Without secrets from their side, our rewrapped key simply wins.
+        let result = merge_files(&base, &ours, &base);
+        assert!(result.rekey.is_empty());
+        assert_eq!(result.file.environments["default"].data_key.as_deref(), Some("wrapped-2"));
+    }
+
+    #[test]
+    fn team_members_merge_by_name() {
+        let base = base_file();
+        let alice = base.team["alice"].clone();
+        let mut ours = base.clone();
+        ours.team.insert(
+            "bob".to_string(),
+            TeamMember { pubkey: "age1bob".to_string(), ..alice.clone() },
+        );
+        let mut theirs = base.clone();
+        theirs.team.insert(
+            "carol".to_string(),
+            TeamMember { pubkey: "age1carol".to_string(), ..alice.clone() },
+        );
+
+        ours.roster = Some(seal(2));
+        theirs.roster = Some(seal(3));
+
+        let result = merge_files(&base, &ours, &theirs);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(result.file.team.keys().collect::<Vec<_>>(), ["alice", "bob", "carol"]);
+        assert!(result.reseal);
+        assert_eq!(result.file.roster, Some(seal(3)));
+
+        // A team changed on one side only keeps that side's seal.
+        let result = merge_files(&base, &base, &ours);
+        assert!(!result.reseal);
+        assert_eq!(result.file.roster, Some(seal(2)));
+
+        theirs
+            .team
+            .insert("bob".to_string(), TeamMember { pubkey: "age1other".to_string(), ..alice });
+        let result = merge_files(&base, &ours, &theirs);
+        assert_eq!(result.conflicts, ["team member bob was changed on both sides"]);
+    }
+}
@@ -1,18 +1,55 @@
 use std::collections::BTreeMap;
 
 use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
 
 use crate::error::{EnvkeyError, Result};
 
-pub const FORMAT_VERSION: u32 = 1;
+pub const FORMAT_VERSION: u32 = 2;
+pub const DEFAULT_ENVIRONMENT: &str = "default";
+/// First format version whose team roster carries admin signatures and a seal, and whose
+/// secrets are encrypted under per-environment data keys.
+pub const SIGNED_FORMAT_VERSION: u32 = 2;
+
+/// `MIGRATIONS[n]` upgrades a raw document from version `n + 1` to `n + 2`.
+const MIGRATIONS: &[fn(&mut Mapping) -> Result<()>] = &[migrate_v1_to_v2];
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EnvkeyFile {
     pub version: u32,
     #[serde(default)]
     pub team: BTreeMap<String, TeamMember>,
+    /// Admin seal over the whole team section; absent in files from before version 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roster: Option<RosterSeal>,
+    #[serde(default)]
+    pub environments: BTreeMap<String, Environment>,
+    /// Format version the file was written with when it was upgraded on read.
+    #[serde(skip)]
+    pub migrated_from: Option<u32>,
+    /// Serial of a newer roster this machine has already read, when this file's roster
+    /// is older than it.
+    #[serde(skip)]
+    pub rolled_back_from: Option<u64>,
+}
+
+/// Signature of an admin over every team entry. The serial grows with each roster
+/// change, so an older roster cannot pass for the current one.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RosterSeal {
+    pub serial: u64,
+    pub signed_by: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Environment {
+    /// Data key for this environment's secrets, age-encrypted to its recipients.
+    /// Absent until the first secret is set, and in files from before version 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_key: Option<String>,
     #[serde(default)]
-    pub environments: BTreeMap<String, BTreeMap<String, SecretEntry>>,
+    pub secrets: BTreeMap<String, SecretEntry>,
 }
 
 impl EnvkeyFile {
@@ -25,16 +62,26 @@ impl EnvkeyFile {
                 role: Role::Admin,
                 added: now_date,
                 environments: None,
+                signed_by: String::new(),
+                signature: String::new(),
             },
         );
 
         let mut environments = BTreeMap::new();
-        environments.insert("default".to_string(), BTreeMap::new());
+        environments.insert(DEFAULT_ENVIRONMENT.to_string(), Environment::default());
 
-        Self { version: FORMAT_VERSION, team, environments }
+        Self {
+            version: FORMAT_VERSION,
+            team,
+            roster: None,
+            environments,
+            migrated_from: None,
+            rolled_back_from: None,
+        }
     }
 
     pub fn ensure_supported_version(&self) -> Result<()> {
+        check_version(self.version)?;
         if self.version != FORMAT_VERSION {
             return Err(EnvkeyError::message(format!(
                 "unsupported .envkey version: {} (supported: {})",
@@ -44,22 +91,144 @@ impl EnvkeyFile {
         Ok(())
     }
 
-    pub fn default_env_mut(&mut self) -> &mut BTreeMap<String, SecretEntry> {
-        self.environments.entry("default".to_string()).or_default()
+    /// Serial of the current roster seal, 0 while the roster is unsealed.
+    pub fn roster_serial(&self) -> u64 {
+        self.roster.as_ref().map_or(0, |seal| seal.serial)
     }
 
-    pub fn default_env(&self) -> Option<&BTreeMap<String, SecretEntry>> {
-        self.environments.get("default")
+    pub fn env(&self, name: &str) -> Option<&BTreeMap<String, SecretEntry>> {
+        self.environments.get(name).map(|env| &env.secrets)
+    }
+
+    pub fn env_mut(&mut self, name: &str) -> &mut BTreeMap<String, SecretEntry> {
+        &mut self.environments.entry(name.to_string()).or_default().secrets
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Upgrades a raw `.envkey` document to [`FORMAT_VERSION`] in place and returns the
+/// version it was written with.
+pub fn migrate_document(doc: &mut Value) -> Result<u32> {
+    let mapping = doc
+        .as_mapping_mut()
+        .ok_or_else(|| EnvkeyError::message("invalid .envkey: expected a YAML mapping"))?;
+    let version = mapping
+        .get("version")
+        .and_then(Value::as_u64)
+        .and_then(|version| u32::try_from(version).ok())
+        .ok_or_else(|| {
+            EnvkeyError::message("invalid .envkey: missing or invalid `version` field")
+        })?;
+    check_version(version)?;
+    check_fields_match_version(mapping, version)?;
+
+    for from in version..FORMAT_VERSION {
+        MIGRATIONS[(from - 1) as usize](mapping)?;
+        mapping.insert(Value::from("version"), Value::from(from + 1));
+    }
+    Ok(version)
+}
+
+fn check_version(version: u32) -> Result<()> {
+    if version == 0 {
+        return Err(EnvkeyError::message("unsupported .envkey version: 0"));
+    }
+    if version > FORMAT_VERSION {
+        return Err(EnvkeyError::message(format!(
+            "unsupported .envkey version: {version} is newer than this envkey supports \
+             (up to {FORMAT_VERSION}); upgrade envkey to read this file"
+        )));
+    }
+    Ok(())
+}
+
+/// Rejects documents holding fields that only later versions write. Claiming an older
+/// version would otherwise skip the roster verification that the newer fields imply.
+fn check_fields_match_version(doc: &Mapping, version: u32) -> Result<()> {
+    let tampered = |detail: String| {
+        EnvkeyError::message(format!(
+            "invalid .envkey: it claims format version {version}, but {detail}; the file may \
+             have been tampered with (ask an admin to review recent changes to it)"
+        ))
+    };
+    let entries = |section: &str| {
+        doc.get(section)
+            .and_then(Value::as_mapping)
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, entry)| Some((name.as_str()?, entry.as_mapping()?)))
+    };
+
+    if version >= SIGNED_FORMAT_VERSION {
+        return Ok(());
+    }
+    for (name, member) in entries("team") {
+        if let Some(field) =
+            ["signed_by", "signature"].into_iter().find(|field| member.contains_key(*field))
+        {
+            return Err(tampered(format!(
+                "team member `{name}` has a `{field}` field, which only exists from version \
+                 {SIGNED_FORMAT_VERSION}"
+            )));
+        }
+    }
+    // Version 1 environments map keys straight to secrets, which are never strings.
+    for (name, env) in entries("environments") {
+        if env.get("data_key").is_some_and(Value::is_string) {
+            return Err(tampered(format!(
+                "environment `{name}` has a `data_key`, which only exists from version \
+                 {SIGNED_FORMAT_VERSION}"
+            )));
+        }
+    }
+    if doc.contains_key("roster") {
+        return Err(tampered(format!(
+            "it has a `roster` seal, which only exists from version {SIGNED_FORMAT_VERSION}"
+        )));
+    }
+    Ok(())
+}
+
+/// Version 2 nests each environment's secrets under `secrets:` so environments can
+/// carry their own metadata, such as the `data_key` its secrets are encrypted under. The
+/// data keys, member signatures and roster seal need an identity, so `envkey migrate` adds
+/// them; until then environments without a `data_key` are read as one age ciphertext per
+/// value.
+fn migrate_v1_to_v2(doc: &mut Mapping) -> Result<()> {
+    let Some(environments) = doc.get_mut("environments") else {
+        return Ok(());
+    };
+    let Some(environments) = environments.as_mapping_mut() else {
+        return Ok(());
+    };
+
+    for (_, env) in environments.iter_mut() {
+        let secrets = std::mem::take(env);
+        let mut wrapped = Mapping::new();
+        wrapped.insert(Value::from("secrets"), secrets);
+        *env = Value::Mapping(wrapped);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct TeamMember {
     pub pubkey: String,
     pub role: Role,
     pub added: String,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub environments: Option<Vec<String>>,
+    /// Admin whose key signed this entry; the root admin signs their own entry.
+    #[serde(default)]
+    pub signed_by: String,
+    #[serde(default)]
+    pub signature: String,
+}
+
+impl TeamMember {
+    /// Members without an explicit environment list can access every environment.
+    pub fn can_access(&self, env_name: &str) -> bool {
+        self.environments.as_ref().is_none_or(|envs| envs.iter().any(|env| env == env_name))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -71,13 +240,30 @@ pub enum Role {
     Readonly,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct SecretEntry {
     pub value: String,
+    /// Absent in files written before binary support; those values are UTF-8.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<SecretEncoding>,
     pub set_by: String,
     pub modified: String,
 }
 
+impl SecretEntry {
+    pub fn encoding(&self) -> SecretEncoding {
+        self.encoding.unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretEncoding {
+    #[default]
+    Utf8,
+    Binary,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -89,10 +275,11 @@ mod tests {
             "age1example".to_string(),
             "2026-02-26".to_string(),
         );
-        file.default_env_mut().insert(
+        file.env_mut("production").insert(
             "API_KEY".to_string(),
             SecretEntry {
                 value: "encrypted".to_string(),
+                encoding: None,
                 set_by: "alice".to_string(),
                 modified: "2026-02-26T00:00:00Z".to_string(),
             },
@@ -103,14 +290,103 @@ mod tests {
 
         assert_eq!(parsed.version, FORMAT_VERSION);
         assert!(parsed.team.contains_key("alice"));
-        assert!(parsed.default_env().expect("default env").contains_key("API_KEY"));
+        assert!(parsed.env(DEFAULT_ENVIRONMENT).expect("default env").is_empty());
+        assert!(parsed.env("production").expect("production env").contains_key("API_KEY"));
+    }
+
+    #[test]
+    fn secret_encoding_defaults_to_utf8() {
+        let entry: SecretEntry =
+            serde_yaml::from_str("value: abc\nset_by: alice\nmodified: now\n").expect("parse");
+        assert_eq!(entry.encoding(), SecretEncoding::Utf8);
+
+        let binary = SecretEntry { encoding: Some(SecretEncoding::Binary), ..entry };
+        let yaml = serde_yaml::to_string(&binary).expect("serialize");
+        assert!(yaml.contains("encoding: binary"));
+    }
+
+    #[test]
+    fn member_environment_access() {
+        let mut member = TeamMember {
+            pubkey: "age1example".to_string(),
+            role: Role::Member,
+            added: "2026-02-26".to_string(),
+            environments: None,
+            signed_by: String::new(),
+            signature: String::new(),
+        };
+        assert!(member.can_access("production"));
+
+        member.environments = Some(vec!["staging".to_string()]);
+        assert!(member.can_access("staging"));
+        assert!(!member.can_access("production"));
     }
 
     #[test]
     fn version_guard_rejects_unknown_version() {
-        let file = EnvkeyFile { version: 99, team: BTreeMap::new(), environments: BTreeMap::new() };
+        let file = EnvkeyFile {
+            version: 99,
+            team: BTreeMap::new(),
+            roster: None,
+            environments: BTreeMap::new(),
+            migrated_from: None,
+            rolled_back_from: None,
+        };
 
         let err = file.ensure_supported_version().expect_err("must fail");
         assert!(err.to_string().contains("unsupported .envkey version: 99"));
+        assert!(err.to_string().contains("upgrade envkey"));
+    }
+
+    #[test]
+    fn migrates_v1_documents_to_current_version() {
+        let mut doc: Value = serde_yaml::from_str(
+            "version: 1\nteam: {}\nenvironments:\n  default:\n    API_KEY:\n      value: abc\n      set_by: alice\n      modified: now\n  empty: {}\n",
+        )
+        .expect("parse");
+
+        let original = migrate_document(&mut doc).expect("migrate");
+        let file: EnvkeyFile = serde_yaml::from_value(doc).expect("deserialize");
+
+        assert_eq!(original, 1);
+        assert_eq!(file.version, FORMAT_VERSION);
+        assert_eq!(file.env("default").expect("default env")["API_KEY"].value, "abc");
+        assert!(file.env("empty").expect("empty env").is_empty());
+    }
+
+    #[test]
+    fn migration_rejects_fields_newer_than_the_claimed_version() {
+        let mut signed: Value = serde_yaml::from_str(
+            "version: 1\nteam:\n  mallory:\n    pubkey: age1example\n    role: admin\n    added: now\n    signed_by: mallory\n    signature: abc\n",
+        )
+        .expect("parse");
+        let err = migrate_document(&mut signed).expect_err("must fail");
+        assert!(err.to_string().contains("claims format version 1"));
+        assert!(err.to_string().contains("team member `mallory` has a `signed_by` field"));
+
+        let mut enveloped: Value = serde_yaml::from_str(
+            "version: 1\nenvironments:\n  default:\n    data_key: abc\n    secrets: {}\n",
+        )
+        .expect("parse");
+        let err = migrate_document(&mut enveloped).expect_err("must fail");
+        assert!(err.to_string().contains("environment `default` has a `data_key`"));
+
+        let mut sealed: Value = serde_yaml::from_str(
+            "version: 1\nroster:\n  serial: 1\n  signed_by: mallory\n  signature: abc\n",
+        )
+        .expect("parse");
+        let err = migrate_document(&mut sealed).expect_err("must fail");
+        assert!(err.to_string().contains("it has a `roster` seal"));
+    }
+
+    #[test]
+    fn migration_rejects_newer_and_missing_versions() {
+        let mut newer: Value = serde_yaml::from_str("version: 99\n").expect("parse");
+        let err = migrate_document(&mut newer).expect_err("must fail");
+        assert!(err.to_string().contains("newer than this envkey supports"));
+
+        let mut missing: Value = serde_yaml::from_str("team: {}\n").expect("parse");
+        let err = migrate_document(&mut missing).expect_err("must fail");
+        assert!(err.to_string().contains("missing or invalid `version` field"));
     }
 }
@@ -0,0 +1,317 @@
+//! Structured results printed by `--output-format json` and `--output-format yaml`.
+//!
+//! Each command emits one of the report types below. Field names and value types are a
+//! stable interface: new fields may be added, but existing ones are not renamed or removed.
+
+use serde::Serialize;
+
+use crate::error::{EnvkeyError, Result};
+use crate::model::{Role, SecretEncoding};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Yaml,
+}
+
+impl OutputFormat {
+    pub fn is_table(self) -> bool {
+        self == OutputFormat::Table
+    }
+
+    /// Prints `report` in the selected structured format, or runs `table` to print the
+    /// human-readable form.
+    pub fn print<T: Serialize>(self, report: &T, table: impl FnOnce()) -> Result<()> {
+        match self {
+            OutputFormat::Table => table(),
+            OutputFormat::Json | OutputFormat::Yaml => print!("{}", render(self, report)?),
+        }
+        Ok(())
+    }
+}
+
+pub fn render<T: Serialize>(format: OutputFormat, report: &T) -> Result<String> {
+    match format {
+        OutputFormat::Table => Err(EnvkeyError::message("table output has no serialized form")),
+        OutputFormat::Json => {
+            let mut json = serde_json::to_string_pretty(report).map_err(|err| {
+                EnvkeyError::message(format!("failed to serialize JSON output: {err}"))
+            })?;
+            json.push('\n');
+            Ok(json)
+        }
+        OutputFormat::Yaml => serde_yaml::to_string(report)
+            .map_err(|err| EnvkeyError::message(format!("failed to serialize YAML output: {err}"))),
+    }
+}
+
+/// `envkey init`
+#[derive(Debug, Serialize)]
+pub struct InitReport {
+    pub identity: String,
+    pub identity_generated: bool,
+    pub passphrase_protected: bool,
+    pub envkey: String,
+    pub envkey_created: bool,
+    pub public_key: String,
+}
+
+/// Metadata of one stored secret, as listed by `ls` and returned by `set` and `get`.
+#[derive(Debug, Serialize)]
+pub struct SecretInfo {
+    pub environment: String,
+    pub key: String,
+    pub encoding: SecretEncoding,
+    pub set_by: String,
+    pub modified: String,
+}
+
+/// `envkey set`
+#[derive(Debug, Serialize)]
+pub struct SetReport {
+    #[serde(flatten)]
+    pub secret: SecretInfo,
+    pub recipients: usize,
+}
+
+/// `envkey get`. `value` is base64 for binary secrets and `null` when the value was
+/// written to `written_to` instead.
+#[derive(Debug, Serialize)]
+pub struct GetReport {
+    #[serde(flatten)]
+    pub secret: SecretInfo,
+    pub value: Option<String>,
+    pub written_to: Option<String>,
+}
+
+/// `envkey ls`
+#[derive(Debug, Serialize)]
+pub struct LsReport {
+    pub secrets: Vec<SecretInfo>,
+}
+
+/// One entry of `envkey ls --all-projects`; counts are `null` when the file is missing.
+#[derive(Debug, Serialize)]
+pub struct ProjectInfo {
+    pub name: String,
+    pub path: String,
+    pub environments: Option<usize>,
+    pub secrets: Option<usize>,
+    pub members: Option<usize>,
+}
+
+/// `envkey ls --all-projects`
+#[derive(Debug, Serialize)]
+pub struct ProjectsReport {
+    pub projects: Vec<ProjectInfo>,
+}
+
+/// What happened to one key in `import`, `promote` and `edit`: `added`, `changed`,
+/// `unchanged`, `skipped` or `removed`.
+#[derive(Debug, Serialize)]
+pub struct KeyChange {
+    pub key: String,
+    pub status: &'static str,
+}
+
+/// `envkey import`
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub environment: String,
+    pub path: String,
+    pub dry_run: bool,
+    pub changes: Vec<KeyChange>,
+}
+
+/// `envkey promote`
+#[derive(Debug, Serialize)]
+pub struct PromoteReport {
+    pub from: String,
+    pub to: String,
+    pub dry_run: bool,
+    pub changes: Vec<KeyChange>,
+}
+
+/// `envkey edit`
+#[derive(Debug, Serialize)]
+pub struct EditReport {
+    pub environment: String,
+    pub changes: Vec<KeyChange>,
+}
+
+/// Keys removed from one environment by `envkey rm`.
+#[derive(Debug, Serialize)]
+pub struct RemovedKeys {
+    pub environment: String,
+    pub keys: Vec<String>,
+}
+
+/// `envkey rm`
+#[derive(Debug, Serialize)]
+pub struct RmReport {
+    pub removed: Vec<RemovedKeys>,
+}
+
+/// `envkey mv`
+#[derive(Debug, Serialize)]
+pub struct MvReport {
+    pub old: String,
+    pub new: String,
+    pub environments: Vec<String>,
+}
+
+/// `envkey export --output`; without a file the export itself is printed.
+#[derive(Debug, Serialize)]
+pub struct ExportReport {
+    pub environment: String,
+    pub secrets: usize,
+    pub written_to: String,
+}
+
+/// A decrypted value shown by `envkey diff --show-values`; binary values are base64.
+#[derive(Debug, Serialize)]
+pub struct DiffValue {
+    pub value: String,
+    pub encoding: SecretEncoding,
+}
+
+/// A key that differs between two sides of `envkey diff`: `added`, `removed` or
+/// `changed`. Values are only present with `--show-values`.
+#[derive(Debug, Serialize)]
+pub struct DiffEntry {
+    pub key: String,
+    pub status: &'static str,
+    pub old: Option<DiffValue>,
+    pub new: Option<DiffValue>,
+}
+
+/// One comparison made by `envkey diff`. `environment` is `null` when two environments
+/// are compared, and `from`/`to` then name them.
+#[derive(Debug, Serialize)]
+pub struct DiffComparison {
+    pub environment: Option<String>,
+    pub from: String,
+    pub to: String,
+    pub changes: Vec<DiffEntry>,
+}
+
+/// `envkey diff`. `not_readable` lists the environments a diff over every environment
+/// skipped because the identity cannot decrypt them on one side or both.
+#[derive(Debug, Serialize)]
+pub struct DiffReport {
+    pub comparisons: Vec<DiffComparison>,
+    pub not_readable: Vec<String>,
+}
+
+/// Secrets moved under a new data key in one environment.
+#[derive(Debug, Serialize)]
+pub struct Reencrypted {
+    pub environment: String,
+    pub secrets: usize,
+}
+
+/// `envkey migrate`. `from_version` equals `to_version` when nothing had to change.
+#[derive(Debug, Serialize)]
+pub struct MigrateReport {
+    pub from_version: u32,
+    pub to_version: u32,
+    pub signed_members: Vec<String>,
+    pub reencrypted: Vec<Reencrypted>,
+}
+
+/// `envkey trust`
+#[derive(Debug, Serialize)]
+pub struct TrustReport {
+    pub envkey: String,
+    pub root: String,
+    pub admins: Vec<String>,
+}
+
+/// `envkey rotate`
+#[derive(Debug, Serialize)]
+pub struct RotateReport {
+    #[serde(flatten)]
+    pub reencrypted: Reencrypted,
+    pub recipients: usize,
+}
+
+/// `envkey identity passwd`
+#[derive(Debug, Serialize)]
+pub struct IdentityReport {
+    pub identity: String,
+    pub public_key: String,
+    pub passphrase_protected: bool,
+}
+
+/// `envkey git install`. `attributes_added` is the `.gitattributes` line written, if any.
+#[derive(Debug, Serialize)]
+pub struct GitInstallReport {
+    pub attributes_added: Option<String>,
+}
+
+/// One entry of `envkey member ls`; `environments` is `null` for access to all.
+#[derive(Debug, Serialize)]
+pub struct MemberInfo {
+    pub name: String,
+    pub pubkey: String,
+    pub role: Role,
+    pub environments: Option<Vec<String>>,
+    pub added: String,
+    pub signed_by: String,
+}
+
+/// `envkey member ls`
+#[derive(Debug, Serialize)]
+pub struct MembersReport {
+    pub members: Vec<MemberInfo>,
+}
+
+/// `envkey member add|update|rm|grant|revoke|role set`.
+///
+/// `action` is the subcommand (`add`, `update`, `rm`, `grant`, `revoke`, `role-set`),
+/// `environments` the ones granted or revoked, `rewrapped` the environments whose data key
+/// was re-encrypted, and `rotated` those whose data key was replaced because the member lost
+/// access to it or replaced their key.
+/// `ci_private_key` is only set when `member add --role ci` generated a key pair.
+#[derive(Debug, Serialize)]
+pub struct MemberReport {
+    pub action: &'static str,
+    pub name: String,
+    pub role: Option<Role>,
+    pub environments: Vec<String>,
+    pub rewrapped: Vec<String>,
+    pub rotated: Vec<String>,
+    pub ci_private_key: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flattened_reports_render_as_flat_objects() {
+        let report = SetReport {
+            secret: SecretInfo {
+                environment: "default".to_string(),
+                key: "API_KEY".to_string(),
+                encoding: SecretEncoding::Utf8,
+                set_by: "alice".to_string(),
+                modified: "2026-02-26T00:00:00Z".to_string(),
+            },
+            recipients: 2,
+        };
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render(OutputFormat::Json, &report).expect("json"))
+                .expect("parse");
+        assert_eq!(json["key"], "API_KEY");
+        assert_eq!(json["encoding"], "utf8");
+        assert_eq!(json["recipients"], 2);
+
+        let yaml = render(OutputFormat::Yaml, &report).expect("yaml");
+        assert!(yaml.contains("environment: default\n"));
+        assert!(render(OutputFormat::Table, &report).is_err());
+    }
+}
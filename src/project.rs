@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::{EnvkeyError, Result};
+use crate::storage::find_in_ancestors;
+
+pub const PROJECT_FILE_NAME: &str = "envkey.toml";
+
+/// `envkey.toml` names the `.envkey` files of a monorepo, so `--project` can select one from
+/// anywhere in the repository. Paths are relative to the directory holding `envkey.toml`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    #[serde(default)]
+    projects: BTreeMap<String, ProjectEntry>,
+    #[serde(skip)]
+    root: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectEntry {
+    path: PathBuf,
+}
+
+impl ProjectConfig {
+    /// Loads the nearest `envkey.toml` in `cwd` or its parents, up to the repository root.
+    pub fn discover(cwd: &Path) -> Result<Option<Self>> {
+        find_in_ancestors(cwd, PROJECT_FILE_NAME).map(|path| Self::load(&path)).transpose()
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path).map_err(|err| {
+            EnvkeyError::message(format!("failed to read {}: {err}", path.display()))
+        })?;
+        let mut config: Self = toml::from_str(&raw).map_err(|err| {
+            EnvkeyError::message(format!(
+                "invalid {PROJECT_FILE_NAME} in {}: {err}",
+                path.display()
+            ))
+        })?;
+        config.root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(config)
+    }
+
+    pub fn envkey_path(&self, name: &str) -> Result<PathBuf> {
+        let entry = self.projects.get(name).ok_or_else(|| {
+            let known: Vec<&str> = self.projects.keys().map(String::as_str).collect();
+            EnvkeyError::message(format!(
+                "project not found: {name} (known projects: {})",
+                if known.is_empty() { "none".to_string() } else { known.join(", ") }
+            ))
+        })?;
+        Ok(self.root.join(&entry.path))
+    }
+
+    /// Project names with the `.envkey` path as written in `envkey.toml` and as resolved.
+    pub fn projects(&self) -> impl Iterator<Item = (&str, &Path, PathBuf)> {
+        self.projects
+            .iter()
+            .map(|(name, entry)| (name.as_str(), entry.path.as_path(), self.root.join(&entry.path)))
+    }
+}
+
+/// Resolves `--project NAME` against the `envkey.toml` that applies to `cwd`.
+pub fn project_envkey_path(cwd: &Path, name: &str) -> Result<PathBuf> {
+    let config = ProjectConfig::discover(cwd)?.ok_or_else(|| {
+        EnvkeyError::message(format!(
+            "--project needs an {PROJECT_FILE_NAME} in {} or its parent directories",
+            cwd.display()
+        ))
+    })?;
+    config.envkey_path(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn projects_resolve_relative_to_the_config() {
+        let temp = tempdir().expect("tempdir");
+        let nested = temp.path().join("services").join("api");
+        fs::create_dir_all(&nested).expect("mkdir");
+        fs::write(
+            temp.path().join(PROJECT_FILE_NAME),
+            "[projects.api]\npath = \"services/api/.envkey\"\n\n[projects.web]\npath = \"web/.envkey\"\n",
+        )
+        .expect("write");
+
+        let config = ProjectConfig::discover(&nested).expect("load").expect("config found");
+        assert_eq!(config.envkey_path("api").expect("api"), nested.join(".envkey"));
+        assert_eq!(
+            project_envkey_path(&nested, "web").expect("web"),
+            temp.path().join("web").join(".envkey")
+        );
+
+        let err = config.envkey_path("worker").expect_err("must fail");
+        assert!(err.to_string().contains("project not found: worker (known projects: api, web)"));
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let temp = tempdir().expect("tempdir");
+        let path = temp.path().join(PROJECT_FILE_NAME);
+        fs::write(&path, "[projects.api]\nfile = \".envkey\"\n").expect("write");
+
+        let err = ProjectConfig::load(&path).expect_err("must fail");
+        assert!(err.to_string().contains("invalid envkey.toml"));
+    }
+}
@@ -0,0 +1,201 @@
+use age::x25519;
+
+use crate::crypto::{sign_message, verify_signature};
+use crate::error::{EnvkeyError, Result};
+use crate::model::{EnvkeyFile, Role, RosterSeal, TeamMember};
+
+/// Checks the member signatures (see [`verify_members`]) and the admin seal over the
+/// whole team section.
+pub fn verify_roster(file: &EnvkeyFile) -> Result<()> {
+    verify_members(file)?;
+    check_seal(file).map_err(tampered)
+}
+
+/// Checks that every team member is signed by an admin whose own entry checks out,
+/// and that all signature chains end at a single self-signed root admin.
+pub fn verify_members(file: &EnvkeyFile) -> Result<()> {
+    for (name, member) in &file.team {
+        check_entry(file, name, member).map_err(tampered)?;
+    }
+
+    let roots: Vec<&str> = file
+        .team
+        .iter()
+        .filter(|(name, member)| member.signed_by == **name)
+        .map(|(name, _)| name.as_str())
+        .collect();
+    if roots.len() > 1 {
+        return Err(tampered(format!(
+            "more than one admin signed their own entry ({})",
+            roots.join(", ")
+        )));
+    }
+
+    for name in file.team.keys() {
+        let mut current = name.as_str();
+        let mut hops = 0;
+        while file.team[current].signed_by != current {
+            current = &file.team[current].signed_by;
+            hops += 1;
+            if hops > file.team.len() {
+                return Err(tampered(format!(
+                    "team member `{name}` is not signed by a chain leading to the root admin"
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Signs `name`'s current entry as `signer`.
+pub fn sign_member(
+    file: &mut EnvkeyFile,
+    name: &str,
+    signer: &str,
+    identity: &x25519::Identity,
+) -> Result<()> {
+    let member = file
+        .team
+        .get(name)
+        .ok_or_else(|| EnvkeyError::message(format!("team member not found: {name}")))?;
+    let signature = sign_message(identity, &signing_payload(name, member, signer))?;
+
+    let member = file.team.get_mut(name).expect("member exists");
+    member.signed_by = signer.to_string();
+    member.signature = signature;
+    Ok(())
+}
+
+/// Seals the team section as admin `actor` under the next roster serial. Member
+/// signatures alone cannot tell a current entry from an old one pasted back in.
+pub fn seal_roster(file: &mut EnvkeyFile, actor: &str, identity: &x25519::Identity) -> Result<()> {
+    ensure_current_roster(file)?;
+    let serial = file.roster_serial() + 1;
+    let signature = sign_message(identity, &seal_payload(file, serial, actor))?;
+    file.roster = Some(RosterSeal { serial, signed_by: actor.to_string(), signature });
+    Ok(())
+}
+
+/// Refuses to build on a roster older than one this machine has already read, since an
+/// admin may have removed someone from it since.
+pub fn ensure_current_roster(file: &EnvkeyFile) -> Result<()> {
+    let Some(newer) = file.rolled_back_from else {
+        return Ok(());
+    };
+    Err(EnvkeyError::message(format!(
+        "the team roster (serial {}) is older than one this machine has already read (serial \
+         {newer}), as after a rollback of .envkey or checking out an older branch; refusing to \
+         encrypt to it or change it (switch to the newest roster, or run `envkey trust` if going \
+         back is intended)",
+        file.roster_serial()
+    )))
+}
+
+/// Re-signs, as admin `actor`, every entry that no longer verifies after a roster
+/// change, then seals the result. If the actor's own signer is gone, the actor becomes
+/// the root admin and the previous root is countersigned so the roster keeps a single
+/// root.
+pub fn reseal_roster(
+    file: &mut EnvkeyFile,
+    actor: &str,
+    identity: &x25519::Identity,
+) -> Result<()> {
+    let actor_member = file
+        .team
+        .get(actor)
+        .ok_or_else(|| EnvkeyError::message(format!("team member not found: {actor}")))?;
+    if check_entry(file, actor, actor_member).is_err() {
+        sign_member(file, actor, actor, identity)?;
+    }
+
+    let names: Vec<String> = file.team.keys().cloned().collect();
+    for name in names.iter().filter(|name| name.as_str() != actor) {
+        let member = &file.team[name];
+        let self_signed = member.signed_by == *name && file.team[actor].signed_by == actor;
+        if self_signed || check_entry(file, name, member).is_err() {
+            sign_member(file, name, actor, identity)?;
+        }
+    }
+
+    verify_members(file)?;
+    seal_roster(file, actor, identity)
+}
+
+fn check_entry(file: &EnvkeyFile, name: &str, member: &TeamMember) -> Result<()> {
+    if member.signed_by.is_empty() || member.signature.is_empty() {
+        return Err(EnvkeyError::message(format!("team member `{name}` has no admin signature")));
+    }
+
+    let signer = &member.signed_by;
+    let Some(signer_member) = file.team.get(signer) else {
+        return Err(EnvkeyError::message(format!(
+            "team member `{name}` is signed by `{signer}`, who is not on the team"
+        )));
+    };
+    if signer_member.role != Role::Admin {
+        return Err(EnvkeyError::message(format!(
+            "team member `{name}` is signed by `{signer}`, who is not an admin"
+        )));
+    }
+
+    let payload = signing_payload(name, member, signer);
+    if !verify_signature(&signer_member.pubkey, &payload, &member.signature) {
+        return Err(EnvkeyError::message(format!(
+            "team member `{name}` has an invalid signature from `{signer}`"
+        )));
+    }
+    Ok(())
+}
+
+fn check_seal(file: &EnvkeyFile) -> Result<()> {
+    let Some(seal) = &file.roster else {
+        return Err(EnvkeyError::message("the team roster has no admin seal"));
+    };
+    let signer = &seal.signed_by;
+    let Some(signer_member) = file.team.get(signer) else {
+        return Err(EnvkeyError::message(format!(
+            "the team roster is sealed by `{signer}`, who is not on the team"
+        )));
+    };
+    if signer_member.role != Role::Admin {
+        return Err(EnvkeyError::message(format!(
+            "the team roster is sealed by `{signer}`, who is not an admin"
+        )));
+    }
+
+    let payload = seal_payload(file, seal.serial, signer);
+    if !verify_signature(&signer_member.pubkey, &payload, &seal.signature) {
+        return Err(EnvkeyError::message(format!(
+            "the team roster does not match its seal from `{signer}`; entries were changed, \
+             added or restored after it was sealed"
+        )));
+    }
+    Ok(())
+}
+
+/// Canonical bytes covered by a roster signature: everything that decides who can
+/// decrypt what. The JSON array encoding keeps field boundaries unambiguous.
+fn signing_payload(name: &str, member: &TeamMember, signer: &str) -> Vec<u8> {
+    serde_json::to_vec(&(
+        "envkey-roster-v1",
+        name,
+        &member.pubkey,
+        &member.role,
+        &member.environments,
+        signer,
+    ))
+    .expect("roster payload serializes")
+}
+
+/// Canonical bytes covered by the roster seal: the serial and every team entry as written.
+fn seal_payload(file: &EnvkeyFile, serial: u64, signer: &str) -> Vec<u8> {
+    serde_json::to_vec(&("envkey-roster-seal-v1", serial, signer, &file.team))
+        .expect("roster seal payload serializes")
+}
+
+fn tampered(detail: impl std::fmt::Display) -> EnvkeyError {
+    EnvkeyError::message(format!(
+        "roster verification failed: {detail}; the .envkey team section may have been tampered \
+         with (ask an admin to review recent changes to it)"
+    ))
+}
@@ -1,4 +1,6 @@
+use std::env;
 use std::fs::{self, OpenOptions};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
 use fs2::FileExt;
@@ -6,7 +8,10 @@ use rand::distr::Alphanumeric;
 use rand::{Rng, rng};
 
 use crate::error::{EnvkeyError, Result};
-use crate::model::EnvkeyFile;
+use crate::identity::expand_home_prefix;
+use crate::model::{EnvkeyFile, FORMAT_VERSION, SIGNED_FORMAT_VERSION, migrate_document};
+use crate::roster::verify_roster;
+use crate::trust::check_known_roster;
 
 pub const ENVKEY_FILE_NAME: &str = ".envkey";
 
@@ -14,17 +19,108 @@ pub fn envkey_path(cwd: &Path) -> PathBuf {
     cwd.join(ENVKEY_FILE_NAME)
 }
 
+/// Finds the `.envkey` that applies to `cwd` the way git finds `.git`: the nearest ancestor
+/// holding one, searching no higher than the repository root. Falls back to `cwd/.envkey`,
+/// which is where `envkey init` creates it.
+pub fn discover_envkey_path(cwd: &Path) -> PathBuf {
+    find_in_ancestors(cwd, ENVKEY_FILE_NAME).unwrap_or_else(|| envkey_path(cwd))
+}
+
+/// Returns the nearest `file_name` in `cwd` or its parents, stopping at the directory that
+/// holds `.git`.
+pub fn find_in_ancestors(cwd: &Path, file_name: &str) -> Option<PathBuf> {
+    for dir in cwd.ancestors() {
+        let candidate = dir.join(file_name);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    None
+}
+
+/// Returns the file named by `--file` or `ENVKEY_FILE`, if either is set.
+pub fn explicit_envkey_path(cli_override: Option<&Path>) -> Result<Option<PathBuf>> {
+    cli_override
+        .map(Path::to_path_buf)
+        .or_else(|| env::var_os("ENVKEY_FILE").map(PathBuf::from))
+        .map(|path| expand_home_prefix(&path))
+        .transpose()
+}
+
+pub fn missing_envkey(path: &Path) -> EnvkeyError {
+    EnvkeyError::message(format!("missing .envkey at {}; run `envkey init` first", path.display()))
+}
+
 pub fn read_envkey(path: &Path) -> Result<EnvkeyFile> {
+    let file = read_unverified_envkey(path)?;
+    verify_envkey_roster(&file)?;
+    Ok(file)
+}
+
+/// Reads `path` like [`read_envkey`] but leaves the team roster unverified, for callers
+/// that only display the file and report a failed [`verify_envkey_roster`] themselves.
+pub fn read_unverified_envkey(path: &Path) -> Result<EnvkeyFile> {
     let raw = fs::read_to_string(path)
         .map_err(|err| EnvkeyError::message(format!("failed to read {}: {err}", path.display())))?;
-    let file: EnvkeyFile = serde_yaml::from_str(&raw).map_err(|err| {
-        EnvkeyError::message(format!("invalid .envkey YAML in {}: {err}", path.display()))
-    })?;
+    parse_unverified_envkey(&raw, &path.display().to_string())
+}
+
+/// Reads the project's `.envkey`, also checking its roster against the admins this machine
+/// trusted for it before (see [`check_known_roster`]). Copies handed over by git are read
+/// with [`read_envkey`] instead.
+pub fn read_trusted_envkey(path: &Path) -> Result<EnvkeyFile> {
+    let mut file = read_envkey(path)?;
+    check_known_roster(path, &mut file)?;
+    Ok(file)
+}
+
+/// Parses `.envkey` content read from `source` (a path, or a description such as a git
+/// revision), upgrading older formats in memory and verifying the team roster.
+pub fn parse_envkey(raw: &str, source: &str) -> Result<EnvkeyFile> {
+    let file = parse_unverified_envkey(raw, source)?;
+    verify_envkey_roster(&file)?;
+    Ok(file)
+}
+
+fn parse_unverified_envkey(raw: &str, source: &str) -> Result<EnvkeyFile> {
+    let invalid = |err: serde_yaml::Error| {
+        EnvkeyError::message(format!("invalid .envkey YAML in {source}: {err}"))
+    };
+
+    let mut doc: serde_yaml::Value = serde_yaml::from_str(raw).map_err(invalid)?;
+    let original_version = migrate_document(&mut doc)?;
+    let mut file: EnvkeyFile = serde_yaml::from_value(doc).map_err(invalid)?;
     file.ensure_supported_version()?;
+    if original_version < FORMAT_VERSION {
+        file.migrated_from = Some(original_version);
+    }
     Ok(file)
 }
 
+/// Verifies the team roster of a parsed file. Rosters written before signing existed have
+/// nothing to verify yet; they stay read-only until `envkey migrate` signs them.
+pub fn verify_envkey_roster(file: &EnvkeyFile) -> Result<()> {
+    if file.migrated_from.unwrap_or(file.version) >= SIGNED_FORMAT_VERSION {
+        verify_roster(file)?;
+    }
+    Ok(())
+}
+
+/// Writes `file` via a temporary file and rename. Files that were upgraded in memory
+/// are refused so a routine command never bumps the format under teammates running
+/// older envkey releases; `envkey migrate` is the explicit upgrade path.
 pub fn write_envkey_atomic(path: &Path, file: &EnvkeyFile) -> Result<()> {
+    if let Some(version) = file.migrated_from {
+        return Err(EnvkeyError::message(format!(
+            "{} uses .envkey format version {version}; run `envkey migrate` to upgrade it to \
+             version {FORMAT_VERSION} before making changes",
+            path.display()
+        )));
+    }
+
     let yaml = serde_yaml::to_string(file)
         .map_err(|err| EnvkeyError::message(format!("failed to serialize .envkey: {err}")))?;
 
@@ -34,7 +130,7 @@ pub fn write_envkey_atomic(path: &Path, file: &EnvkeyFile) -> Result<()> {
     fs::create_dir_all(parent)?;
 
     let suffix: String = rng().sample_iter(Alphanumeric).map(char::from).take(8).collect();
-    let tmp = parent.join(format!("{}.tmp.{}", ENVKEY_FILE_NAME, suffix));
+    let tmp = parent.join(format!("{}.tmp.{}", file_name(path), suffix));
 
     fs::write(&tmp, yaml.as_bytes()).map_err(|err| {
         EnvkeyError::message(format!("failed to write temporary file {}: {err}", tmp.display()))
@@ -48,12 +144,41 @@ pub fn write_envkey_atomic(path: &Path, file: &EnvkeyFile) -> Result<()> {
     Ok(())
 }
 
+/// Writes `contents` to `path`, readable and writable only by the current user.
+pub fn write_private_file(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.create(true).write(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    file.flush()?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+
+    Ok(())
+}
+
 pub fn with_envkey_lock<T>(path: &Path, action: impl FnOnce() -> Result<T>) -> Result<T> {
     let parent = path
         .parent()
         .ok_or_else(|| EnvkeyError::message(".envkey path has no parent directory"))?;
     fs::create_dir_all(parent)?;
-    let lock_path = parent.join(format!("{ENVKEY_FILE_NAME}.lock"));
+    let lock_path = parent.join(format!("{}.lock", file_name(path)));
 
     let lock_file = OpenOptions::new()
         .create(true)
@@ -71,6 +196,11 @@ pub fn with_envkey_lock<T>(path: &Path, action: impl FnOnce() -> Result<T>) -> R
     action()
 }
 
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(|| ENVKEY_FILE_NAME.to_string(), |name| name.to_string_lossy().into_owned())
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeMap;
@@ -78,6 +208,7 @@ mod tests {
     use tempfile::tempdir;
 
     use crate::model::{SecretEntry, TeamMember};
+    use crate::roster::{seal_roster, sign_member};
 
     use super::*;
 
@@ -85,22 +216,34 @@ mod tests {
     fn write_and_read_round_trip() {
         let temp = tempdir().expect("tempdir");
         let path = envkey_path(temp.path());
+        let identity = age::x25519::Identity::generate();
 
-        let mut file =
-            EnvkeyFile { version: 1, team: BTreeMap::new(), environments: BTreeMap::new() };
+        let mut file = EnvkeyFile {
+            version: FORMAT_VERSION,
+            team: BTreeMap::new(),
+            roster: None,
+            environments: BTreeMap::new(),
+            migrated_from: None,
+            rolled_back_from: None,
+        };
         file.team.insert(
             "alice".to_string(),
             TeamMember {
-                pubkey: "age1example".to_string(),
+                pubkey: identity.to_public().to_string(),
                 role: crate::model::Role::Admin,
                 added: "2026-02-26".to_string(),
                 environments: None,
+                signed_by: String::new(),
+                signature: String::new(),
             },
         );
-        file.default_env_mut().insert(
+        sign_member(&mut file, "alice", "alice", &identity).expect("sign");
+        seal_roster(&mut file, "alice", &identity).expect("seal");
+        file.env_mut("default").insert(
             "API_KEY".to_string(),
             SecretEntry {
                 value: "encrypted".to_string(),
+                encoding: None,
                 set_by: "alice".to_string(),
                 modified: "2026-02-26T00:00:00Z".to_string(),
             },
@@ -110,7 +253,126 @@ mod tests {
         let loaded = read_envkey(&path).expect("read");
 
         assert!(loaded.team.contains_key("alice"));
-        assert!(loaded.default_env().expect("default env").contains_key("API_KEY"));
+        assert!(loaded.env("default").expect("default env").contains_key("API_KEY"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_files_are_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempdir().expect("tempdir");
+        let path = temp.path().join("out").join("secrets.env");
+
+        write_private_file(&path, b"A=1\n").expect("write private");
+
+        assert_eq!(fs::read_to_string(&path).expect("read"), "A=1\n");
+        let mode = fs::metadata(&path).expect("metadata").permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+    }
+
+    #[test]
+    fn older_versions_are_upgraded_on_read_but_not_written_implicitly() {
+        let temp = tempdir().expect("tempdir");
+        let path = envkey_path(temp.path());
+        fs::write(
+            &path,
+            "version: 1\nteam: {}\nenvironments:\n  default:\n    API_KEY:\n      value: abc\n      set_by: alice\n      modified: now\n",
+        )
+        .expect("write");
+
+        let mut loaded = read_envkey(&path).expect("read");
+        assert_eq!(loaded.migrated_from, Some(1));
+        assert!(loaded.env("default").expect("default env").contains_key("API_KEY"));
+
+        let err = write_envkey_atomic(&path, &loaded).expect_err("must refuse");
+        assert!(err.to_string().contains("run `envkey migrate`"));
+
+        loaded.migrated_from = None;
+        write_envkey_atomic(&path, &loaded).expect("write");
+        let written = fs::read_to_string(&path).expect("read");
+        assert!(written.starts_with(&format!("version: {FORMAT_VERSION}\n")));
+    }
+
+    #[test]
+    fn tampered_roster_is_rejected_on_read() {
+        let temp = tempdir().expect("tempdir");
+        let path = envkey_path(temp.path());
+        let alice = age::x25519::Identity::generate();
+
+        let mut file = EnvkeyFile::new(
+            "alice".to_string(),
+            alice.to_public().to_string(),
+            "2026-02-26".to_string(),
+        );
+        sign_member(&mut file, "alice", "alice", &alice).expect("sign");
+        seal_roster(&mut file, "alice", &alice).expect("seal");
+        write_envkey_atomic(&path, &file).expect("write");
+
+        file.team.insert(
+            "mallory".to_string(),
+            TeamMember {
+                pubkey: age::x25519::Identity::generate().to_public().to_string(),
+                role: crate::model::Role::Admin,
+                added: "2026-02-26".to_string(),
+                environments: None,
+                signed_by: String::new(),
+                signature: String::new(),
+            },
+        );
+        write_envkey_atomic(&path, &file).expect("write");
+
+        let err = read_envkey(&path).expect_err("must fail");
+        assert!(err.to_string().contains("team member `mallory` has no admin signature"));
+    }
+
+    #[test]
+    fn restored_member_entry_breaks_the_roster_seal() {
+        let temp = tempdir().expect("tempdir");
+        let path = envkey_path(temp.path());
+        let alice = age::x25519::Identity::generate();
+
+        let mut file = EnvkeyFile::new(
+            "alice".to_string(),
+            alice.to_public().to_string(),
+            "2026-02-26".to_string(),
+        );
+        sign_member(&mut file, "alice", "alice", &alice).expect("sign");
+        let mut bob = file.team["alice"].clone();
+        bob.pubkey = age::x25519::Identity::generate().to_public().to_string();
+        bob.role = crate::model::Role::Member;
+        file.team.insert("bob".to_string(), bob);
+        sign_member(&mut file, "bob", "alice", &alice).expect("sign bob");
+        seal_roster(&mut file, "alice", &alice).expect("seal");
+        let signed_bob = file.team["bob"].clone();
+
+        file.team.remove("bob");
+        seal_roster(&mut file, "alice", &alice).expect("seal");
+        assert_eq!(file.roster_serial(), 2);
+        file.team.insert("bob".to_string(), signed_bob);
+        write_envkey_atomic(&path, &file).expect("write");
+
+        let err = read_envkey(&path).expect_err("must fail");
+        assert!(err.to_string().contains("does not match its seal from `alice`"));
+    }
+
+    #[test]
+    fn discovery_walks_up_to_the_repository_root() {
+        let temp = tempdir().expect("tempdir");
+        let repo = temp.path().join("repo");
+        let nested = repo.join("services").join("api");
+        fs::create_dir_all(&nested).expect("mkdir");
+        fs::create_dir(repo.join(".git")).expect("mkdir .git");
+
+        assert_eq!(discover_envkey_path(&nested), envkey_path(&nested));
+
+        fs::write(envkey_path(&repo), "").expect("write");
+        assert_eq!(discover_envkey_path(&nested), envkey_path(&repo));
+
+        // A file above the repository root belongs to some other project.
+        fs::remove_file(envkey_path(&repo)).expect("remove");
+        fs::write(envkey_path(temp.path()), "").expect("write");
+        assert_eq!(discover_envkey_path(&nested), envkey_path(&nested));
     }
 
     #[test]
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{EnvkeyError, Result};
+use crate::identity::default_identity_path;
+use crate::model::{EnvkeyFile, Role};
+use crate::storage::write_private_file;
+
+/// Admins of every `.envkey` this machine has read, keyed by the file's canonical path.
+/// A roster can only vouch for itself, so this is what anchors it: whoever edits the file
+/// cannot also edit every teammate's copy of this.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct KnownRosters {
+    #[serde(default)]
+    rosters: BTreeMap<String, KnownRoster>,
+}
+
+/// A roster as it was last trusted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownRoster {
+    /// Admin names and public keys.
+    pub admins: BTreeMap<String, String>,
+    /// Serial of the roster seal; a lower one later means the roster was rolled back.
+    #[serde(default)]
+    pub serial: u64,
+}
+
+impl KnownRoster {
+    pub fn of(file: &EnvkeyFile) -> Self {
+        let admins = file
+            .team
+            .iter()
+            .filter(|(_, member)| member.role == Role::Admin)
+            .map(|(name, member)| (name.clone(), member.pubkey.clone()))
+            .collect();
+        Self { admins, serial: file.roster_serial() }
+    }
+}
+
+pub fn known_rosters_path() -> Result<PathBuf> {
+    Ok(default_identity_path()?.with_file_name("known_rosters.yaml"))
+}
+
+/// Checks that the root admin of `file` was an admin when this machine last read
+/// `envkey_path`, then remembers the current admins and roster serial. The root's
+/// self-signature then comes from a key trusted before, so a team section replaced
+/// wholesale is refused. A roster older than the remembered one is marked in
+/// `rolled_back_from` and not remembered. A file read for the first time is trusted as it is.
+/// Remembering is best-effort, so read-only commands still work from a read-only home.
+pub fn check_known_roster(envkey_path: &Path, file: &mut EnvkeyFile) -> Result<()> {
+    let mut known = KnownRosters::load()?;
+    let key = roster_key(envkey_path);
+    let root = file
+        .team
+        .iter()
+        .find(|(name, member)| member.signed_by == **name && !member.signature.is_empty());
+
+    match (known.rosters.get(&key), root) {
+        // Rosters from before signing have nothing to check until `envkey migrate`.
+        (None, None) => return Ok(()),
+        (Some(_), None) => {
+            return Err(untrusted(envkey_path, "its team roster is no longer signed"));
+        }
+        (Some(pinned), Some((name, member)))
+            if !pinned.admins.values().any(|pubkey| *pubkey == member.pubkey) =>
+        {
+            return Err(untrusted(
+                envkey_path,
+                &format!(
+                    "its root admin is now `{name}` ({}), who was not an admin when this \
+                     machine last read it",
+                    member.pubkey
+                ),
+            ));
+        }
+        (Some(pinned), Some(_)) if file.roster_serial() < pinned.serial => {
+            file.rolled_back_from = Some(pinned.serial);
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let current = KnownRoster::of(file);
+    if known.rosters.get(&key) != Some(&current) {
+        known.rosters.insert(key, current);
+        if let Err(err) = known.save() {
+            eprintln!(
+                "warning: could not remember the team roster of {}: {err}",
+                envkey_path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Trusts the roster of `file` as it is, whatever this machine trusted before, including
+/// an older roster serial.
+pub fn trust_roster(envkey_path: &Path, file: &EnvkeyFile) -> Result<()> {
+    let mut known = KnownRosters::load()?;
+    known.rosters.insert(roster_key(envkey_path), KnownRoster::of(file));
+    known.save()
+}
+
+impl KnownRosters {
+    fn load() -> Result<Self> {
+        let path = known_rosters_path()?;
+        let raw = match fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(err)
+                if matches!(err.kind(), io::ErrorKind::NotFound | io::ErrorKind::NotADirectory) =>
+            {
+                return Ok(Self::default());
+            }
+            Err(err) => {
+                return Err(EnvkeyError::message(format!(
+                    "failed to read {}: {err}",
+                    path.display()
+                )));
+            }
+        };
+        serde_yaml::from_str(&raw).map_err(|err| {
+            EnvkeyError::message(format!("invalid known rosters file {}: {err}", path.display()))
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        let yaml = serde_yaml::to_string(self).map_err(|err| {
+            EnvkeyError::message(format!("failed to serialize known rosters: {err}"))
+        })?;
+        write_private_file(&known_rosters_path()?, yaml.as_bytes())
+    }
+}
+
+fn roster_key(envkey_path: &Path) -> String {
+    fs::canonicalize(envkey_path)
+        .unwrap_or_else(|_| envkey_path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn untrusted(envkey_path: &Path, detail: &str) -> EnvkeyError {
+    EnvkeyError::message(format!(
+        "roster verification failed for {}: {detail}; the team section may have been replaced \
+         (ask an admin to review recent changes to it, and run `envkey trust` once you have \
+         confirmed the new admins with your team)",
+        envkey_path.display()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_roster_lists_only_admins() {
+        let mut file =
+            EnvkeyFile::new("alice".to_string(), "age1alice".to_string(), "2026-02-26".to_string());
+        let mut bob = file.team["alice"].clone();
+        bob.pubkey = "age1bob".to_string();
+        bob.role = Role::Member;
+        file.team.insert("bob".to_string(), bob);
+
+        let known = KnownRoster::of(&file);
+        assert_eq!(known.admins, BTreeMap::from([("alice".to_string(), "age1alice".to_string())]));
+        assert_eq!(known.serial, 0);
+    }
+}